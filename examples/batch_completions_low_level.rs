@@ -17,36 +17,36 @@ async fn main() -> anyhow::Result<()> {
     let requests = vec![
         BatchRequestItem::new_chat(
             "request-1",
-            ChatRequest {
-                model: "gpt-3.5-turbo".to_string(),
-                messages: vec![
+            ChatRequest::new(
+                "gpt-3.5-turbo",
+                vec![
                     ChatMessage::system("You are a helpful assistant."),
                     ChatMessage::user("What is the capital of France?"),
                 ],
-                response_format: ResponseFormat::Text,
-            },
+                ResponseFormat::Text,
+            ),
         ),
         BatchRequestItem::new_chat(
             "request-2",
-            ChatRequest {
-                model: "gpt-3.5-turbo".to_string(),
-                messages: vec![
+            ChatRequest::new(
+                "gpt-3.5-turbo",
+                vec![
                     ChatMessage::system("You are a helpful assistant."),
                     ChatMessage::user("What is the capital of Japan?"),
                 ],
-                response_format: ResponseFormat::Text,
-            },
+                ResponseFormat::Text,
+            ),
         ),
         BatchRequestItem::new_chat(
             "request-3",
-            ChatRequest {
-                model: "gpt-3.5-turbo".to_string(),
-                messages: vec![
+            ChatRequest::new(
+                "gpt-3.5-turbo",
+                vec![
                     ChatMessage::system("You are a helpful assistant."),
                     ChatMessage::user("What is the capital of Italy?"),
                 ],
-                response_format: ResponseFormat::Text,
-            },
+                ResponseFormat::Text,
+            ),
         ),
     ];
 
@@ -0,0 +1,551 @@
+//! Transcribing and translating speech.
+//! This module provides a client for interacting with the OpenAI Audio API (Whisper).
+//!
+//! ```rust,no_run
+//! # use tysm::audio::{AudioClient, TranscriptionOptions};
+//! # tokio_test::block_on(async {
+//! let client = AudioClient::from_env("whisper-1").unwrap();
+//! let transcription = client
+//!     .transcribe("interview.mp3", &TranscriptionOptions::default())
+//!     .await
+//!     .unwrap();
+//! println!("{}", transcription.text);
+//! # })
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use reqwest::{multipart, Client};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{
+    utils::{api_key, base_url, file_part, Authenticate, Authentication, OpenAiApiKeyError},
+    OpenAiError,
+};
+
+/// A client for interacting with the OpenAI Audio API.
+pub struct AudioClient {
+    /// The API key to use for the Audio API.
+    pub api_key: String,
+    /// The URL of the API. Customize this if you are using a custom API that is compatible with OpenAI's.
+    pub base_url: url::Url,
+    /// The subpath to the audio endpoints. By default, this is `audio/`.
+    pub audio_path: String,
+    /// The model to use, such as `whisper-1` or `gpt-4o-transcribe`.
+    pub model: String,
+    /// How requests are authenticated. By default, this is [`Authentication::Bearer`].
+    pub authentication: Authentication,
+    /// The directory in which to cache transcriptions and translations.
+    pub cache_directory: Option<PathBuf>,
+}
+
+/// Audio to transcribe or translate: either a file on disk, or bytes in memory.
+#[derive(Debug, Clone)]
+pub enum AudioFile {
+    /// A file on disk. Unless the client has a cache directory, it is streamed to the API without being read into memory.
+    Path(PathBuf),
+    /// Audio in memory.
+    Bytes {
+        /// The name of the file, such as `speech.mp3`. The API uses its extension to tell the format.
+        filename: String,
+        /// The contents of the file.
+        bytes: Vec<u8>,
+    },
+}
+
+impl AudioFile {
+    /// Audio in memory. The API uses the extension of `filename` to tell the format.
+    pub fn from_bytes(filename: impl Into<String>, bytes: Vec<u8>) -> Self {
+        AudioFile::Bytes {
+            filename: filename.into(),
+            bytes,
+        }
+    }
+}
+
+impl From<&Path> for AudioFile {
+    fn from(path: &Path) -> Self {
+        AudioFile::Path(path.to_path_buf())
+    }
+}
+
+impl From<PathBuf> for AudioFile {
+    fn from(path: PathBuf) -> Self {
+        AudioFile::Path(path)
+    }
+}
+
+impl From<&str> for AudioFile {
+    fn from(path: &str) -> Self {
+        AudioFile::Path(path.into())
+    }
+}
+
+/// The format of a transcription or translation.
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioResponseFormat {
+    /// Just the text.
+    #[default]
+    Json,
+    /// The text, along with the language, the duration, and timestamped segments (and words, if requested).
+    VerboseJson,
+    /// SubRip subtitles.
+    Srt,
+    /// WebVTT subtitles.
+    Vtt,
+    /// Plain text.
+    Text,
+}
+
+impl std::fmt::Display for AudioResponseFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioResponseFormat::Json => write!(f, "json"),
+            AudioResponseFormat::VerboseJson => write!(f, "verbose_json"),
+            AudioResponseFormat::Srt => write!(f, "srt"),
+            AudioResponseFormat::Vtt => write!(f, "vtt"),
+            AudioResponseFormat::Text => write!(f, "text"),
+        }
+    }
+}
+
+/// How finely a [`AudioResponseFormat::VerboseJson`] transcription is timestamped.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampGranularity {
+    /// Timestamps for each segment (the default).
+    Segment,
+    /// Timestamps for each word.
+    Word,
+}
+
+impl std::fmt::Display for TimestampGranularity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimestampGranularity::Segment => write!(f, "segment"),
+            TimestampGranularity::Word => write!(f, "word"),
+        }
+    }
+}
+
+/// Options for transcribing or translating audio. Options left as `None` use the API's defaults.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct TranscriptionOptions {
+    /// The format of the result.
+    pub response_format: AudioResponseFormat,
+    /// The language of the audio, as an ISO-639-1 code (such as `en`). Setting it improves accuracy and latency.
+    /// Translations are always into English, so this is ignored for them.
+    pub language: Option<String>,
+    /// Text to guide the model's style, or to continue a previous segment. It should be in the language of the audio
+    /// (or in English, for translations).
+    pub prompt: Option<String>,
+    /// The sampling temperature, between 0 and 1.
+    pub temperature: Option<f32>,
+    /// How finely to timestamp the transcription. Requires [`AudioResponseFormat::VerboseJson`].
+    pub timestamp_granularities: Vec<TimestampGranularity>,
+}
+
+impl TranscriptionOptions {
+    /// Set the format of the result.
+    pub fn with_response_format(mut self, response_format: AudioResponseFormat) -> Self {
+        self.response_format = response_format;
+        self
+    }
+
+    /// Set the language of the audio, as an ISO-639-1 code (such as `en`).
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Set the text that guides the model's style.
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Set the sampling temperature.
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Timestamp each word, as well as each segment. This also switches to [`AudioResponseFormat::VerboseJson`].
+    pub fn with_word_timestamps(mut self) -> Self {
+        self.response_format = AudioResponseFormat::VerboseJson;
+        self.timestamp_granularities =
+            vec![TimestampGranularity::Segment, TimestampGranularity::Word];
+        self
+    }
+
+    fn form_fields(&self, translation: bool) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("response_format", self.response_format.to_string())];
+        if let (Some(language), false) = (&self.language, translation) {
+            fields.push(("language", language.clone()));
+        }
+        if let Some(prompt) = &self.prompt {
+            fields.push(("prompt", prompt.clone()));
+        }
+        if let Some(temperature) = self.temperature {
+            fields.push(("temperature", temperature.to_string()));
+        }
+        for granularity in &self.timestamp_granularities {
+            fields.push(("timestamp_granularities[]", granularity.to_string()));
+        }
+        fields
+    }
+}
+
+/// A transcription or translation.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Transcription {
+    /// The text. For [`AudioResponseFormat::Srt`] and [`AudioResponseFormat::Vtt`], these are the subtitles.
+    pub text: String,
+    /// The language of the audio. Only returned with [`AudioResponseFormat::VerboseJson`].
+    #[serde(default)]
+    pub language: Option<String>,
+    /// The duration of the audio, in seconds. Only returned with [`AudioResponseFormat::VerboseJson`].
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// The timestamped segments of the text. Only returned with [`AudioResponseFormat::VerboseJson`].
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+    /// The timestamped words of the text. Only returned if word timestamps were requested
+    /// (see [`TranscriptionOptions::with_word_timestamps`]).
+    #[serde(default)]
+    pub words: Vec<Word>,
+}
+
+/// A segment of a transcription.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Segment {
+    /// The index of the segment.
+    pub id: u32,
+    /// When the segment starts, in seconds.
+    pub start: f64,
+    /// When the segment ends, in seconds.
+    pub end: f64,
+    /// The text of the segment.
+    pub text: String,
+    /// The average log probability of the segment's tokens. Below -1, the segment is probably wrong.
+    #[serde(default)]
+    pub avg_logprob: f64,
+    /// How compressible the segment's text is. Above 2.4, the segment is probably repetitive nonsense.
+    #[serde(default)]
+    pub compression_ratio: f64,
+    /// The probability that the segment is silent. Above 1 and with a low `avg_logprob`, the segment is probably silence.
+    #[serde(default)]
+    pub no_speech_prob: f64,
+}
+
+/// A word of a transcription.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Word {
+    /// The word.
+    pub word: String,
+    /// When the word starts, in seconds.
+    pub start: f64,
+    /// When the word ends, in seconds.
+    pub end: f64,
+}
+
+#[derive(Debug, Deserialize)]
+enum TranscriptionOrError {
+    #[serde(rename = "error")]
+    Error(OpenAiError),
+    #[serde(untagged)]
+    Transcription(Transcription),
+}
+
+/// Errors that can occur when interacting with the Audio API.
+#[derive(Error, Debug)]
+pub enum AudioError {
+    /// An error occurred when sending the request to the API.
+    #[error("Request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    /// An error occurred when deserializing the response from the API.
+    #[error("API {url} returned an unknown response: {response}")]
+    ApiParseError {
+        /// The URL of the API that returned the error.
+        url: String,
+        /// The response from the API.
+        response: String,
+        /// The error that occurred when deserializing the response.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// The API returned an error.
+    #[error("API returned an error response")]
+    ApiError(#[source] OpenAiError),
+
+    /// An error occurred when reading the audio, or when reading from or writing to the cache.
+    #[error("File error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// The file path is invalid.
+    #[error("Invalid file path")]
+    InvalidFilePath,
+}
+
+impl AudioClient {
+    /// Create a new [`AudioClient`].
+    /// If the API key is in the environment, you can use the [`Self::from_env`] method instead.
+    ///
+    /// ```rust
+    /// use tysm::audio::AudioClient;
+    ///
+    /// let client = AudioClient::new("sk-1234567890", "whisper-1");
+    /// ```
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: "https://api.openai.com/v1/".parse().unwrap(),
+            audio_path: "audio/".into(),
+            model: model.into(),
+            authentication: Authentication::Bearer,
+            cache_directory: None,
+        }
+    }
+
+    /// Create a new [`AudioClient`].
+    /// This will use the `OPENAI_API_KEY` environment variable to set the API key.
+    /// It will also look in the `.env` file for an `OPENAI_API_KEY` variable (using dotenv).
+    ///
+    /// ```rust
+    /// # use tysm::audio::AudioClient;
+    /// let client = AudioClient::from_env("whisper-1").unwrap();
+    /// ```
+    pub fn from_env(model: impl Into<String>) -> Result<Self, OpenAiApiKeyError> {
+        Ok(Self::new(api_key()?, model))
+    }
+
+    /// Sets the base URL
+    ///
+    /// Panics if the argument is not a valid URL.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = base_url(url);
+        self
+    }
+
+    /// Set the cache directory for the client.
+    ///
+    /// Results are cached by a hash of the audio's contents (along with the model and the options),
+    /// so the same audio is only sent once, even if it is renamed or moved.
+    pub fn with_cache_directory(mut self, cache_directory: impl Into<PathBuf>) -> Self {
+        let cache_directory = cache_directory.into();
+
+        if cache_directory.exists() && cache_directory.is_file() {
+            panic!("Cache directory is a file");
+        }
+
+        self.cache_directory = Some(cache_directory);
+        self
+    }
+
+    fn audio_url(&self, endpoint: &str) -> url::Url {
+        self.base_url
+            .join(&self.audio_path)
+            .unwrap()
+            .join(endpoint)
+            .unwrap()
+    }
+
+    /// Transcribe audio into text, in the language of the audio.
+    pub async fn transcribe(
+        &self,
+        audio: impl Into<AudioFile>,
+        options: &TranscriptionOptions,
+    ) -> Result<Transcription, AudioError> {
+        self.send("transcriptions", audio.into(), options).await
+    }
+
+    /// Translate audio into English text. Only `whisper-1` supports this.
+    ///
+    /// ```rust,no_run
+    /// # use tysm::audio::{AudioClient, AudioResponseFormat, TranscriptionOptions};
+    /// # tokio_test::block_on(async {
+    /// let client = AudioClient::from_env("whisper-1").unwrap();
+    /// let options = TranscriptionOptions::default().with_response_format(AudioResponseFormat::Srt);
+    /// let subtitles = client.translate("discours.mp3", &options).await.unwrap();
+    /// std::fs::write("speech.srt", subtitles.text).unwrap();
+    /// # })
+    /// ```
+    pub async fn translate(
+        &self,
+        audio: impl Into<AudioFile>,
+        options: &TranscriptionOptions,
+    ) -> Result<Transcription, AudioError> {
+        self.send("translations", audio.into(), options).await
+    }
+
+    async fn send(
+        &self,
+        endpoint: &str,
+        audio: AudioFile,
+        options: &TranscriptionOptions,
+    ) -> Result<Transcription, AudioError> {
+        // the audio has to be read to be hashed, so it's only streamed when there is no cache
+        let audio = match (audio, &self.cache_directory) {
+            (AudioFile::Path(path), Some(_)) => {
+                let filename = file_name(&path)?;
+                AudioFile::from_bytes(filename, tokio::fs::read(&path).await?)
+            }
+            (audio, _) => audio,
+        };
+        let cache_key = match &audio {
+            AudioFile::Bytes { bytes, .. } => Some(self.cache_key(endpoint, bytes, options)),
+            AudioFile::Path(_) => None,
+        };
+
+        if let Some(transcription) = self.cached(cache_key.as_deref()).await {
+            debug!("Using cached transcription");
+            return Ok(transcription);
+        }
+
+        let file_part = match audio {
+            AudioFile::Path(path) => file_part(&path, file_name(&path)?).await?,
+            AudioFile::Bytes { filename, bytes } => {
+                multipart::Part::bytes(bytes).file_name(filename)
+            }
+        };
+        let mut form = multipart::Form::new()
+            .text("model", self.model.clone())
+            .part("file", file_part);
+        for (name, value) in options.form_fields(endpoint == "translations") {
+            form = form.text(name, value);
+        }
+
+        let url = self.audio_url(endpoint);
+        let response = Client::new()
+            .post(url.clone())
+            .authenticate(&self.authentication, &self.api_key)
+            .multipart(form)
+            .send()
+            .await?;
+        let status = response.status();
+        let response_text = response.text().await?;
+
+        let json = matches!(
+            options.response_format,
+            AudioResponseFormat::Json | AudioResponseFormat::VerboseJson
+        );
+        let transcription = if json || !status.is_success() {
+            let response: TranscriptionOrError =
+                serde_json::from_str(&response_text).map_err(|e| AudioError::ApiParseError {
+                    url: url.to_string(),
+                    response: response_text.clone(),
+                    error: e,
+                })?;
+            match response {
+                TranscriptionOrError::Transcription(transcription) => transcription,
+                TranscriptionOrError::Error(error) => return Err(AudioError::ApiError(error)),
+            }
+        } else {
+            Transcription {
+                text: response_text,
+                ..Transcription::default()
+            }
+        };
+
+        if let (Some(cache_directory), Some(cache_key)) = (&self.cache_directory, cache_key) {
+            if !cache_directory.exists() {
+                tokio::fs::create_dir_all(&cache_directory).await?;
+            }
+            let serialized = serde_json::to_string(&transcription).unwrap();
+            let compressed = zstd::encode_all(serialized.as_bytes(), 3)?;
+            tokio::fs::write(cache_directory.join(cache_key), compressed).await?;
+        }
+        Ok(transcription)
+    }
+
+    fn cache_key(&self, endpoint: &str, bytes: &[u8], options: &TranscriptionOptions) -> String {
+        let request = serde_json::json!({
+            "endpoint": endpoint,
+            "model": self.model,
+            // only the fields that are sent, so that options the endpoint ignores don't change the key
+            "options": options.form_fields(endpoint == "translations"),
+            "audio": xxh3_64(bytes),
+        });
+        let id = xxh3_64(request.to_string().as_bytes());
+        format!("tysm-v1-audio_request-{}.zstd", id)
+    }
+
+    async fn cached(&self, cache_key: Option<&str>) -> Option<Transcription> {
+        let path = self.cache_directory.as_ref()?.join(cache_key?);
+        let compressed_data = tokio::fs::read(path).await.ok()?;
+        let decompressed_data = zstd::decode_all(compressed_data.as_slice()).ok()?;
+        serde_json::from_slice(&decompressed_data).ok()
+    }
+}
+
+fn file_name(path: &Path) -> Result<String, AudioError> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or(AudioError::InvalidFilePath)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbose_transcription() {
+        let response = r#"{
+            "task": "transcribe",
+            "language": "english",
+            "duration": 2.5,
+            "text": "Hello there.",
+            "segments": [{
+                "id": 0, "seek": 0, "start": 0.0, "end": 2.5, "text": " Hello there.",
+                "tokens": [50364, 2425], "temperature": 0.0, "avg_logprob": -0.2,
+                "compression_ratio": 0.8, "no_speech_prob": 0.01
+            }],
+            "words": [
+                {"word": "Hello", "start": 0.0, "end": 1.0},
+                {"word": "there", "start": 1.1, "end": 2.4}
+            ]
+        }"#;
+        let TranscriptionOrError::Transcription(transcription) =
+            serde_json::from_str(response).unwrap()
+        else {
+            panic!("expected a transcription");
+        };
+        assert_eq!(transcription.language.as_deref(), Some("english"));
+        assert_eq!(transcription.segments[0].end, 2.5);
+        assert_eq!(transcription.words[1].word, "there");
+
+        let options = TranscriptionOptions::default()
+            .with_language("en")
+            .with_word_timestamps();
+        assert_eq!(
+            options.form_fields(true),
+            vec![
+                ("response_format", "verbose_json".to_string()),
+                ("timestamp_granularities[]", "segment".to_string()),
+                ("timestamp_granularities[]", "word".to_string()),
+            ]
+        );
+
+        let client = AudioClient::new("sk-1234567890", "whisper-1");
+        let key = client.cache_key("transcriptions", b"audio", &options);
+        assert_eq!(key, client.cache_key("transcriptions", b"audio", &options));
+        assert_ne!(key, client.cache_key("translations", b"audio", &options));
+        assert_ne!(key, client.cache_key("transcriptions", b"other", &options));
+        // translations ignore the language
+        assert_eq!(
+            client.cache_key("translations", b"audio", &options),
+            client.cache_key(
+                "translations",
+                b"audio",
+                &options.clone().with_language("fr")
+            )
+        );
+    }
+}
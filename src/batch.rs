@@ -18,7 +18,8 @@ use tokio::time::sleep;
 
 use crate::chat_completions::{ChatClient, ChatRequest};
 use crate::files::{FilePurpose, FilesClient, FilesError};
-use crate::utils::remove_trailing_slash;
+use crate::responses::ResponsesRequest;
+use crate::utils::{base_url, remove_trailing_slash, Authenticate, Authentication};
 use crate::OpenAiError;
 
 /// A client for batching requests to the OpenAI API.
@@ -35,17 +36,28 @@ pub struct BatchClient {
     pub model: String,
     /// The client to use for file operations.
     pub files_client: FilesClient,
+    /// How requests are authenticated. By default, this is [`Authentication::Bearer`].
+    pub authentication: Authentication,
 }
 
 impl From<&ChatClient> for BatchClient {
     fn from(client: &ChatClient) -> Self {
+        let authentication = client.provider.authentication();
+        let (base_url, endpoint) = match authentication {
+            Authentication::Bearer => (client.base_url.clone(), "/v1/chat/completions"),
+            Authentication::Azure { .. } => (
+                client.base_url.join("openai/").unwrap(),
+                "/chat/completions",
+            ),
+        };
         Self {
             api_key: client.api_key.clone(),
-            base_url: client.base_url.clone(),
+            base_url,
             batches_path: "batches/".to_string(),
-            endpoint: "/v1/chat/completions".to_string(),
+            endpoint: endpoint.to_string(),
             model: client.model.clone(),
             files_client: FilesClient::from(client),
+            authentication,
         }
     }
 }
@@ -317,11 +329,7 @@ pub struct BatchList {
 impl BatchRequestItem {
     /// Create a new batch request item for the chat completions API.
     pub fn new_chat(custom_id: impl Into<String>, chat_request: ChatRequest) -> Self {
-        let body = serde_json::json!({
-            "model": chat_request.model,
-            "messages": chat_request.messages,
-            "response_format": chat_request.response_format,
-        });
+        let body = serde_json::to_value(&chat_request).unwrap();
         Self {
             custom_id: custom_id.into(),
             method: "POST".to_string(),
@@ -366,25 +374,48 @@ impl BatchRequestItem {
     }
 
     /// Create a new batch request item for the responses API.
-    pub fn new_response(
-        custom_id: impl Into<String>,
-        model: impl Into<String>,
-        prompt: impl Into<String>,
-    ) -> Self {
+    pub fn new_response(custom_id: impl Into<String>, request: ResponsesRequest) -> Self {
+        let body = serde_json::to_value(&request).unwrap();
         Self {
             custom_id: custom_id.into(),
             method: "POST".to_string(),
             url: "/v1/responses".to_string(),
-            body: serde_json::json!({
-                "model": model.into(),
-                "prompt": prompt.into(),
-                "max_tokens": 1000,
-            }),
+            body,
         }
     }
 }
 
 impl BatchClient {
+    /// Create a new [`BatchClient`] for batching chat completions on [Azure OpenAI](https://learn.microsoft.com/en-us/azure/ai-services/openai/how-to/batch).
+    ///
+    /// `endpoint` is your resource's endpoint (such as `https://my-resource.openai.azure.com/`),
+    /// and `deployment` is the name of a deployment of the "global batch" type.
+    ///
+    /// ```rust
+    /// use tysm::batch::BatchClient;
+    ///
+    /// let client = BatchClient::azure("https://my-resource.openai.azure.com/", "gpt-4o-batch", "2024-10-21", "YOUR AZURE API KEY");
+    /// ```
+    pub fn azure(
+        endpoint: impl Into<String>,
+        deployment: impl Into<String>,
+        api_version: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Self {
+        let endpoint = endpoint.into();
+        let api_key = api_key.into();
+        let api_version = api_version.into();
+        Self {
+            api_key: api_key.clone(),
+            base_url: base_url(endpoint.clone()).join("openai/").unwrap(),
+            batches_path: "batches/".to_string(),
+            endpoint: "/chat/completions".to_string(),
+            model: deployment.into(),
+            files_client: FilesClient::azure(endpoint, api_version.clone(), api_key),
+            authentication: Authentication::Azure { api_version },
+        }
+    }
+
     fn batches_url(&self) -> url::Url {
         self.base_url.join(&self.batches_path).unwrap()
     }
@@ -438,7 +469,7 @@ impl BatchClient {
         let url = remove_trailing_slash(self.batches_url());
         let response = client
             .post(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .header("Content-Type", "application/json")
             .json(&serde_json::json!({
                 "input_file_id": input_file_id.as_ref(),
@@ -478,7 +509,7 @@ impl BatchClient {
         let url = self.batches_url().join(batch_id).unwrap();
         let response = client
             .get(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .header("Content-Type", "application/json")
             .send()
             .await?;
@@ -579,7 +610,7 @@ impl BatchClient {
                     .join("cancel")
                     .unwrap(),
             )
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .header("Content-Type", "application/json")
             .send()
             .await?;
@@ -656,7 +687,7 @@ impl BatchClient {
         let client = Client::new();
         let response = client
             .get(remove_trailing_slash(url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .header("Content-Type", "application/json")
             .send()
             .await?;
@@ -3,9 +3,9 @@
 //!
 //! It also provides a batch API for processing large numbers of requests asynchronously.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 
 use lru::LruCache;
 use reqwest::Client;
@@ -15,10 +15,18 @@ use thiserror::Error;
 use xxhash_rust::const_xxh3::xxh3_64 as const_xxh3;
 
 use crate::batch::{BatchResponseItem, BatchStatus};
+use crate::files::FileObject;
+use crate::logprobs::LogProbs;
+use crate::model_prices::PriceTable;
+use crate::models::{self, Capability, Unsupported};
+use crate::providers::{
+    AnthropicProvider, AzureProvider, ChatProvider, OllamaProvider, OpenAiProvider,
+};
 use crate::schema::OpenAiTransform;
-use crate::utils::{api_key, OpenAiApiKeyError};
+use crate::usage::{BudgetExceeded, Endpoint, UsageLedger, ASSUMED_MAX_COMPLETION_TOKENS};
+use crate::utils::{anthropic_api_key, api_key, base_url, AnthropicApiKeyError, OpenAiApiKeyError};
 use crate::OpenAiError;
-use log::{debug, info};
+use log::{debug, info, warn};
 
 /// To use this library, you need to create a [`ChatClient`]. This contains various information needed to interact with the ChatGPT API,
 /// such as the API key, the model to use, and the URL of the API.
@@ -50,10 +58,81 @@ pub struct ChatClient {
     pub usage: RwLock<ChatUsage>,
     /// The directory in which to cache responses to requests
     pub cache_directory: Option<PathBuf>,
+    /// How the typed `chat*` methods ask the model for structured output.
+    /// By default, this is [`StructuredOutputMode::StrictJsonSchema`].
+    pub structured_output_mode: StructuredOutputMode,
+    /// The provider that requests are sent through. By default, this is [`OpenAiProvider`].
+    pub provider: Arc<dyn ChatProvider>,
+    /// The models (or clients) to try, in order, when a request to [`Self::model`] fails. See [`Self::with_fallbacks`].
+    pub fallbacks: Vec<Fallback>,
+    /// The ledger in which token consumption is recorded, broken down by model and endpoint.
+    /// Unlike [`Self::usage`], this includes batch requests, and it can be shared with other clients (see [`Self::with_ledger`]).
+    pub ledger: Arc<UsageLedger>,
+    /// The maximum number of tokens the model may generate per request. See [`Self::with_max_tokens`].
+    pub max_tokens: Option<u32>,
+    /// How much reasoning models should think before answering. See [`Self::with_reasoning_effort`].
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// The output the model is expected to produce, to speed up the response. See [`Self::with_prediction`].
+    pub prediction: Option<Prediction>,
+    /// How many of the most likely alternatives to return for each token, if log probabilities should be returned at all.
+    /// See [`Self::with_logprobs`].
+    pub logprobs: Option<u8>,
+}
+
+/// Something to try when a request fails. See [`ChatClient::with_fallbacks`].
+pub enum Fallback {
+    /// Retry the request with a different model, through the same provider.
+    Model(String),
+    /// Retry the request through a different client (for example, one that uses a different provider).
+    ///
+    /// The request is sent to the client's model, with the client's own options (such as its `max_tokens` and
+    /// `reasoning_effort`). The client's own fallbacks are not used.
+    Client(Box<ChatClient>),
+}
+
+/// A response from the API, along with information about how it was produced.
+#[derive(Debug, Clone)]
+pub struct ChatCompletion<T> {
+    /// The response.
+    pub value: T,
+    /// The model that produced the response. When using fallbacks, this is not necessarily the client's model.
+    pub model: String,
+    /// The tokens used to produce the response, as reported by the API.
+    pub usage: ChatUsage,
+    /// Whether the response came from the cache. Cached responses don't count towards the client's usage.
+    pub cached: bool,
+    /// The log probabilities of the response's tokens, if they were requested with [`ChatClient::with_logprobs`].
+    pub logprobs: Option<LogProbs>,
+}
+
+impl<T> ChatCompletion<T> {
+    /// How confident the model was in each field of the response, keyed by JSON pointer (such as `/sentiment`).
+    ///
+    /// Returns `None` if log probabilities weren't requested. See [`LogProbs::field_confidence`].
+    pub fn field_confidence(&self) -> Option<BTreeMap<String, f64>> {
+        Some(self.logprobs.as_ref()?.field_confidence())
+    }
+}
+
+/// How the typed methods (such as [`ChatClient::chat`]) ask the model to produce output matching the response type.
+///
+/// Not every provider supports OpenAI's Structured Outputs. For those that don't, the schema can be
+/// put into the system prompt instead, and the JSON will be extracted from the model's reply
+/// (including from inside markdown code fences).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StructuredOutputMode {
+    /// Use [Structured Outputs](https://platform.openai.com/docs/guides/structured-outputs): the schema
+    /// is sent as a `json_schema` response format and enforced by the API.
+    #[default]
+    StrictJsonSchema,
+    /// Use JSON mode (`json_object` response format), and describe the schema in the system prompt.
+    JsonObject,
+    /// Don't constrain the response format at all; only describe the schema in the system prompt.
+    PromptOnly,
 }
 
 /// The role of a message.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Role {
     /// The user is sending the message.
     #[serde(rename = "user")]
@@ -64,6 +143,11 @@ pub enum Role {
     /// The system is sending the message.
     #[serde(rename = "system")]
     System,
+    /// The developer is sending the message. Reasoning models (such as `o1` and `o3`) expect developer
+    /// messages instead of system messages. System messages sent to them are converted automatically
+    /// (see [`ModelInfo::developer_role`](crate::models::ModelInfo::developer_role)).
+    #[serde(rename = "developer")]
+    Developer,
 }
 
 /// A message to send to the ChatGPT API.
@@ -72,6 +156,10 @@ pub struct ChatMessage {
     /// The role of user sending the message.
     pub role: Role,
 
+    /// The name of the participant sending the message, to tell apart participants with the same role.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
     /// The content of the message. It is a vector of [`ChatMessageContent`]s,
     /// which allows you to include images in the message.
     pub content: Vec<ChatMessageContent>,
@@ -80,23 +168,106 @@ pub struct ChatMessage {
 impl ChatMessage {
     /// Create a new [`ChatMessage`].
     pub fn new(role: Role, content: Vec<ChatMessageContent>) -> Self {
-        Self { role, content }
+        Self {
+            role,
+            name: None,
+            content,
+        }
+    }
+
+    /// Set the name of the participant sending the message.
+    ///
+    /// ```rust
+    /// use tysm::chat_completions::ChatMessage;
+    ///
+    /// let transcript = vec![
+    ///     ChatMessage::user("Shall we meet on Monday?").with_name("alice"),
+    ///     ChatMessage::user("Tuesday works better for me.").with_name("bob"),
+    /// ];
+    /// ```
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
     }
 
     /// Create a new [`ChatMessage`] with the user role.
     pub fn user(content: impl Into<String>) -> Self {
         Self {
             role: Role::User,
+            name: None,
             content: vec![ChatMessageContent::Text {
                 text: content.into(),
             }],
         }
     }
 
+    /// Create a new [`ChatMessage`] with the user role, containing text followed by images.
+    ///
+    /// ```rust,no_run
+    /// use tysm::chat_completions::{ChatMessage, ImageDetail, ImageUrl};
+    ///
+    /// let message = ChatMessage::user_with_images(
+    ///     "What's in these images?",
+    ///     [
+    ///         ImageUrl::from_path("cat.png").unwrap(),
+    ///         ImageUrl::new("https://example.com/dog.jpg").with_detail(ImageDetail::Low),
+    ///     ],
+    /// );
+    /// ```
+    pub fn user_with_images(
+        text: impl Into<String>,
+        images: impl IntoIterator<Item = ImageUrl>,
+    ) -> Self {
+        let mut content = vec![ChatMessageContent::Text { text: text.into() }];
+        content.extend(
+            images
+                .into_iter()
+                .map(|image| ChatMessageContent::ImageUrl { image }),
+        );
+        Self {
+            role: Role::User,
+            name: None,
+            content,
+        }
+    }
+
+    /// Create a new [`ChatMessage`] with the user role, containing text followed by files (such as PDFs).
+    ///
+    /// ```rust,no_run
+    /// # tokio_test::block_on(async {
+    /// use tysm::chat_completions::{ChatMessage, FileContent};
+    /// use tysm::files::{FilePurpose, FilesClient};
+    ///
+    /// let files = FilesClient::from_env().unwrap();
+    /// let report = files.upload_file("report.pdf", FilePurpose::UserData).await.unwrap();
+    /// let message = ChatMessage::user_with_files("Summarize this report.", [FileContent::from(&report)]);
+    /// # })
+    /// ```
+    pub fn user_with_files(
+        text: impl Into<String>,
+        files: impl IntoIterator<Item = FileContent>,
+    ) -> Self {
+        let mut content = vec![ChatMessageContent::Text { text: text.into() }];
+        content.extend(
+            files
+                .into_iter()
+                .map(|file| ChatMessageContent::File { file }),
+        );
+        Self {
+            role: Role::User,
+            name: None,
+            content,
+        }
+    }
+
     /// Create a new [`ChatMessage`] with the assistant role.
+    ///
+    /// When this is the last message of a request to Anthropic's models, it is used as a prefill:
+    /// the model continues the message instead of starting a new one.
     pub fn assistant(content: impl Into<String>) -> Self {
         Self {
             role: Role::Assistant,
+            name: None,
             content: vec![ChatMessageContent::Text {
                 text: content.into(),
             }],
@@ -105,18 +276,29 @@ impl ChatMessage {
 
     /// Create a new [`ChatMessage`] with the system role.
     pub fn system(content: impl Into<String>) -> Self {
-        Self {
-            role: Role::System,
-            content: vec![ChatMessageContent::Text {
+        Self::new(
+            Role::System,
+            vec![ChatMessageContent::Text {
                 text: content.into(),
             }],
-        }
+        )
+    }
+
+    /// Create a new [`ChatMessage`] with the developer role.
+    pub fn developer(content: impl Into<String>) -> Self {
+        Self::new(
+            Role::Developer,
+            vec![ChatMessageContent::Text {
+                text: content.into(),
+            }],
+        )
     }
 }
 
 /// The content of a message.
 ///
-/// Currently, only text and image URLs are supported.
+/// Not every model accepts every kind of content. Images need a vision model, audio needs an audio model
+/// (such as `gpt-4o-audio-preview`), and files need a model that accepts files (such as `gpt-4o`).
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ChatMessageContent {
@@ -133,9 +315,7 @@ pub enum ChatMessageContent {
     ///
     /// let base64_image = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNk+A8AAQUBAScY42YAAAAASUVORK5CYII=";
     /// let content = ChatMessageContent::ImageUrl {
-    ///     image: ImageUrl {
-    ///         url: format!("data:image/png;base64,{base64_image}"),
-    ///     },
+    ///     image: ImageUrl::new(format!("data:image/png;base64,{base64_image}")),
     /// };
     /// ```
     ImageUrl {
@@ -143,13 +323,266 @@ pub enum ChatMessageContent {
         #[serde(rename = "image_url")]
         image: ImageUrl,
     },
+    /// Base64-encoded audio.
+    InputAudio {
+        /// The audio.
+        #[serde(rename = "input_audio")]
+        audio: InputAudio,
+    },
+    /// A file, either uploaded through the [`FilesClient`](crate::files::FilesClient) or sent inline.
+    File {
+        /// The file.
+        file: FileContent,
+    },
+}
+
+impl ChatMessageContent {
+    /// A file part. This accepts [`FileContent`]s, or [`FileObject`]s returned by the [`FilesClient`](crate::files::FilesClient).
+    pub fn file(file: impl Into<FileContent>) -> Self {
+        Self::File { file: file.into() }
+    }
+
+    /// An audio part.
+    pub fn audio(audio: InputAudio) -> Self {
+        Self::InputAudio { audio }
+    }
+}
+
+/// Base64-encoded audio, for models that accept audio input.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputAudio {
+    /// The base64-encoded audio.
+    pub data: String,
+    /// The format of the audio.
+    pub format: AudioFormat,
+}
+
+/// The format of input audio.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// WAV
+    Wav,
+    /// MP3
+    Mp3,
+}
+
+impl InputAudio {
+    /// Encode audio in the given format.
+    pub fn from_bytes(bytes: &[u8], format: AudioFormat) -> Self {
+        use base64::Engine;
+
+        Self {
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            format,
+        }
+    }
+
+    /// Read an audio file, and encode it.
+    pub fn from_path(
+        path: impl AsRef<std::path::Path>,
+        format: AudioFormat,
+    ) -> Result<Self, std::io::Error> {
+        Ok(Self::from_bytes(&std::fs::read(path)?, format))
+    }
+}
+
+/// A file in a message. Either `file_id` (for files uploaded with purpose [`UserData`](crate::files::FilePurpose::UserData)),
+/// or `file_data` and `filename` (for inline files) should be set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FileContent {
+    /// The ID of an uploaded file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+    /// The file's contents, as a base64 data URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_data: Option<String>,
+    /// The name of the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+impl FileContent {
+    /// A file that has already been uploaded.
+    pub fn from_id(file_id: impl Into<String>) -> Self {
+        Self {
+            file_id: Some(file_id.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Send a file inline. The MIME type is guessed from the filename's extension.
+    pub fn from_bytes(filename: impl Into<String>, bytes: &[u8]) -> Self {
+        use base64::Engine;
+
+        let filename = filename.into();
+        let extension = filename
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase());
+        let mime = match extension.as_deref() {
+            Some("pdf") => "application/pdf",
+            Some("txt") => "text/plain",
+            Some("md") => "text/markdown",
+            Some("csv") => "text/csv",
+            Some("json") => "application/json",
+            _ => "application/octet-stream",
+        };
+        let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+        Self {
+            file_id: None,
+            file_data: Some(format!("data:{mime};base64,{data}")),
+            filename: Some(filename),
+        }
+    }
+
+    /// Read a file, and send it inline.
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self::from_bytes(filename, &std::fs::read(path)?))
+    }
+}
+
+impl From<&FileObject> for FileContent {
+    fn from(file: &FileObject) -> Self {
+        Self::from_id(file.id.clone())
+    }
 }
 
 /// An image URL. OpenAI will accept a link to an image, or a base64 encoded image.
+///
+/// Use [`ImageUrl::from_path`] or [`ImageUrl::from_bytes`] to encode an image yourself.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ImageUrl {
     /// The image URL.
     pub url: String,
+    /// How closely the model should look at the image. By default, the API chooses ([`ImageDetail::Auto`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<ImageDetail>,
+}
+
+/// How closely the model should look at an image. Low detail uses fewer tokens.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    /// A 512x512 version of the image, for a fixed (small) number of tokens.
+    Low,
+    /// The full image, split into tiles.
+    High,
+    /// Let the API choose, based on the size of the image.
+    Auto,
+}
+
+/// Errors that can occur when encoding an image.
+#[derive(Error, Debug)]
+pub enum ImageError {
+    /// The image could not be read.
+    #[error("Could not read the image: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// The image is not a PNG, JPEG, WebP or GIF.
+    #[error("Unsupported image format (expected PNG, JPEG, WebP or GIF)")]
+    UnsupportedFormat,
+
+    /// The image is a link, rather than a base64-encoded data URL.
+    #[error("The image is not a base64-encoded data URL")]
+    NotInline,
+
+    /// The data URL does not contain valid base64.
+    #[error("Invalid base64 in the image's data URL: {0}")]
+    Base64Error(#[from] base64::DecodeError),
+
+    /// The image could not be decoded or re-encoded.
+    #[cfg(feature = "image")]
+    #[error("Could not process the image: {0}")]
+    ImageError(#[from] image::ImageError),
+}
+
+impl ImageUrl {
+    /// An image at a URL (or a data URL you've built yourself).
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            detail: None,
+        }
+    }
+
+    /// Encode an image as a base64 data URL. The format (PNG, JPEG, WebP or GIF) is detected from the bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ImageError> {
+        use base64::Engine;
+
+        let mime = sniff_image_mime(bytes).ok_or(ImageError::UnsupportedFormat)?;
+        let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+        Ok(Self::new(format!("data:{mime};base64,{data}")))
+    }
+
+    /// Read an image file and encode it as a base64 data URL. The format (PNG, JPEG, WebP or GIF) is detected from the contents.
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Result<Self, ImageError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Set how closely the model should look at the image.
+    pub fn with_detail(mut self, detail: ImageDetail) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// Shrink an inline image so that neither side is longer than `max_dimension` pixels, keeping its aspect ratio.
+    ///
+    /// Images that are already small enough are left alone. JPEGs stay JPEGs; other formats are re-encoded as PNG.
+    /// Requires the `image` feature.
+    #[cfg(feature = "image")]
+    pub fn downscale(self, max_dimension: u32) -> Result<Self, ImageError> {
+        use base64::Engine;
+
+        let Some((mime, data)) = self
+            .url
+            .strip_prefix("data:")
+            .and_then(|url| url.split_once(";base64,"))
+        else {
+            return Err(ImageError::NotInline);
+        };
+        let bytes = base64::engine::general_purpose::STANDARD.decode(data)?;
+        let image = image::load_from_memory(&bytes)?;
+        if image.width() <= max_dimension && image.height() <= max_dimension {
+            return Ok(self);
+        }
+
+        let image = image.resize(
+            max_dimension,
+            max_dimension,
+            image::imageops::FilterType::Triangle,
+        );
+        let format = if mime == "image/jpeg" {
+            image::ImageFormat::Jpeg
+        } else {
+            image::ImageFormat::Png
+        };
+        let mut resized = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut resized, format)?;
+        Ok(Self {
+            detail: self.detail,
+            ..Self::from_bytes(&resized.into_inner())?
+        })
+    }
+}
+
+/// Detect the MIME type of an image from its first few bytes.
+pub(crate) fn sniff_image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
 }
 
 /// A request to the ChatGPT API. You probably will not need to use this directly,
@@ -162,13 +595,120 @@ pub struct ChatRequest {
     pub messages: Vec<ChatMessage>,
     /// The response format to use for the ChatGPT API.
     pub response_format: ResponseFormat,
+    /// The maximum number of tokens the model may generate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// The maximum number of tokens a reasoning model may generate, including its reasoning tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+    /// How much a reasoning model should think before answering.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// The output the model is expected to produce.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prediction: Option<Prediction>,
+    /// Whether to return the log probabilities of the response's tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    /// How many of the most likely alternatives to return for each token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+    /// Biases added to the logits of tokens (by token ID), from -100 (never produce the token) to 100 (always produce it).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<BTreeMap<u32, i32>>,
+}
+
+/// A [predicted output](https://platform.openai.com/docs/guides/predicted-outputs). See [`ChatClient::with_prediction`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Prediction {
+    /// The response is expected to mostly match this text.
+    Content {
+        /// The expected text.
+        content: String,
+    },
+}
+
+/// How much a reasoning model should think before answering. Less effort gives faster and cheaper responses.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    /// Low effort.
+    Low,
+    /// Medium effort. This is the API's default.
+    Medium,
+    /// High effort.
+    High,
 }
 
 impl ChatRequest {
-    fn cache_key(&self) -> String {
-        let serialized = serde_json::to_string(&self).unwrap();
-        let id = const_xxh3(serialized.as_bytes());
-        format!("tysm-v1-chat_request-{}.zstd", id)
+    /// The hash that identifies this request in a batch. It is computed from the serialized request, so that it only
+    /// changes when what is sent changes.
+    pub(crate) fn batch_hash(&self) -> u64 {
+        const_xxh3(serde_json::to_string(self).unwrap().as_bytes())
+    }
+
+    /// Create a request with the given model, messages and response format, and none of the optional fields set.
+    pub fn new(
+        model: impl Into<String>,
+        messages: Vec<ChatMessage>,
+        response_format: ResponseFormat,
+    ) -> Self {
+        Self {
+            model: model.into(),
+            messages,
+            response_format,
+            max_tokens: None,
+            max_completion_tokens: None,
+            reasoning_effort: None,
+            prediction: None,
+            logprobs: None,
+            top_logprobs: None,
+            logit_bias: None,
+        }
+    }
+
+    /// Check that the model supports the features the request uses (see [`crate::models`]).
+    pub(crate) fn check_capabilities(&self) -> Result<(), Unsupported> {
+        if matches!(self.response_format, ResponseFormat::JsonSchema { .. }) {
+            models::require(&self.model, Capability::StructuredOutputs)?;
+        }
+        let has_images = self.messages.iter().any(|message| {
+            message
+                .content
+                .iter()
+                .any(|part| matches!(part, ChatMessageContent::ImageUrl { .. }))
+        });
+        if has_images {
+            models::require(&self.model, Capability::Vision)?;
+        }
+        if self.reasoning_effort.is_some() {
+            models::require(&self.model, Capability::ReasoningEffort)?;
+        }
+        if self.logprobs == Some(true) {
+            models::require(&self.model, Capability::Logprobs)?;
+        }
+        Ok(())
+    }
+
+    /// The maximum number of tokens the model may generate, whichever way it is sent.
+    pub(crate) fn output_limit(&self) -> Option<u32> {
+        self.max_tokens.or(self.max_completion_tokens)
+    }
+
+    /// The most tokens this request could use, for checking it against a budget.
+    ///
+    /// The prompt size is counted with the model's tokenizer (see [`crate::tokens::count`]). If `max_tokens` isn't set,
+    /// [`ASSUMED_MAX_COMPLETION_TOKENS`] is used.
+    pub(crate) fn worst_case_usage(&self) -> ChatUsage {
+        let prompt_tokens = crate::tokens::count(&self.model, &self.messages);
+        let completion_tokens = self.output_limit().unwrap_or(ASSUMED_MAX_COMPLETION_TOKENS);
+        ChatUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            ..ChatUsage::default()
+        }
     }
 }
 
@@ -246,9 +786,12 @@ pub struct SchemaFormat {
     pub schema: Schema,
 }
 
+/// A message returned by the ChatGPT API.
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub(crate) struct ChatMessageResponse {
+pub struct ChatMessageResponse {
+    /// The role of the author of the message (usually [`Role::Assistant`]).
     pub role: Role,
+    /// The content of the message.
     pub content: Option<String>,
 
     /// When using Structured Outputs with user-generated input, OpenAI models may occasionally refuse to fulfill the request for safety reasons. Since a refusal does not necessarily follow the schema supplied in response_format, the API response will include a new field called refusal to indicate that the model refused to fulfill the request.
@@ -257,7 +800,7 @@ pub(crate) struct ChatMessageResponse {
 }
 
 impl ChatMessageResponse {
-    fn content(self) -> Result<String, String> {
+    pub(crate) fn content(self) -> Result<String, String> {
         if let Some(refusal) = self.refusal {
             if !refusal.trim().is_empty() {
                 return Err(refusal);
@@ -271,44 +814,53 @@ impl ChatMessageResponse {
     }
 }
 
+/// A response from the ChatGPT API. You probably will not need to use this directly,
+/// but it is public because [`ChatProvider`]s parse responses into it.
 #[derive(Deserialize, Debug, Clone)]
-struct ChatResponse {
-    #[expect(unused)]
-    id: String,
-    #[expect(unused)]
-    object: String,
-    #[expect(unused)]
-    created: u64,
-    #[expect(unused)]
-    model: String,
-    #[expect(unused)]
-    system_fingerprint: Option<String>,
-    choices: Vec<ChatChoice>,
-    usage: ChatUsage,
+pub struct ChatResponse {
+    /// The ID of the completion.
+    pub id: String,
+    /// The object type, always "chat.completion".
+    pub object: String,
+    /// When the completion was created (as a unix timestamp).
+    pub created: u64,
+    /// The model that generated the completion.
+    pub model: String,
+    /// A fingerprint of the backend configuration that the model runs with.
+    pub system_fingerprint: Option<String>,
+    /// The generated completions. `tysm` only ever requests one.
+    pub choices: Vec<ChatChoice>,
+    /// The token consumption of the request.
+    pub usage: ChatUsage,
 }
 
+/// One of the completions in a [`ChatResponse`].
 #[derive(Deserialize, Debug, Clone)]
-struct ChatChoice {
-    #[expect(unused)]
-    index: u8,
-    message: ChatMessageResponse,
-    #[expect(unused)]
-    logprobs: Option<serde_json::Value>,
-    #[expect(unused)]
-    finish_reason: String,
+pub struct ChatChoice {
+    /// The index of the choice in the list of choices.
+    pub index: u8,
+    /// The message generated by the model.
+    pub message: ChatMessageResponse,
+    /// Log probability information for the choice, if it was requested.
+    pub logprobs: Option<LogProbs>,
+    /// The reason the model stopped generating tokens.
+    pub finish_reason: String,
 }
 
+/// Either a response or an error, as returned by the API.
 #[derive(Deserialize, Debug)]
-enum ChatResponseOrError {
+pub enum ChatResponseOrError {
+    /// The API returned an error.
     #[serde(rename = "error")]
     Error(OpenAiError),
 
+    /// The API returned a response.
     #[serde(untagged)]
     Response(ChatResponse),
 }
 
 /// The token consumption of the chat-completions API.
-#[derive(Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
 pub struct ChatUsage {
     /// The number of tokens used for the prompt.
     pub prompt_tokens: u32,
@@ -318,27 +870,49 @@ pub struct ChatUsage {
     pub total_tokens: u32,
 
     /// Details about the prompt tokens (such as whether they were cached).
-    #[serde(default)]
+    #[serde(default, alias = "prompt_tokens_details")]
     pub prompt_token_details: Option<PromptTokenDetails>,
     /// Details about the completion tokens for reasoning models
-    #[serde(default)]
+    #[serde(default, alias = "completion_tokens_details")]
     pub completion_token_details: Option<CompletionTokenDetails>,
 }
 
-/// Includes details about the prompt tokens.
-/// Currently, only contains the number of cached tokens.
-#[derive(Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+impl ChatUsage {
+    /// The number of tokens a reasoning model spent thinking. These are included in [`Self::completion_tokens`],
+    /// and billed as output tokens.
+    pub fn reasoning_tokens(&self) -> u32 {
+        self.completion_token_details
+            .map(|details| details.reasoning_tokens)
+            .unwrap_or_default()
+    }
+}
+
+/// Includes details about the prompt tokens: how many were cached, and how many were audio or images.
+/// All of these are included in [`ChatUsage::prompt_tokens`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[serde(default)]
 pub struct PromptTokenDetails {
     /// OpenAI automatically caches tokens that are used in a previous request.
     /// This reduces input cost.
     pub cached_tokens: u32,
+    /// The number of audio input tokens.
+    pub audio_tokens: u32,
+    /// The number of image input tokens. Only reported by some endpoints (such as image generation).
+    pub image_tokens: u32,
+    /// The number of tokens written to the prompt cache. Only reported by Anthropic, which charges more for them
+    /// than for other input tokens.
+    pub cache_write_tokens: u32,
 }
 
-/// Includes details about the completion tokens for reasoning models
-#[derive(Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+/// Includes details about the completion tokens for reasoning models.
+/// All of these are included in [`ChatUsage::completion_tokens`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[serde(default)]
 pub struct CompletionTokenDetails {
     /// The number of tokens used for reasoning.
     pub reasoning_tokens: u32,
+    /// The number of audio output tokens.
+    pub audio_tokens: u32,
     /// The number of accepted tokens from the reasoning model.
     pub accepted_prediction_tokens: u32,
     /// The number of rejected tokens from the reasoning model.
@@ -374,6 +948,9 @@ impl std::ops::Add for PromptTokenDetails {
     fn add(self, rhs: Self) -> Self::Output {
         Self {
             cached_tokens: self.cached_tokens + rhs.cached_tokens,
+            audio_tokens: self.audio_tokens + rhs.audio_tokens,
+            image_tokens: self.image_tokens + rhs.image_tokens,
+            cache_write_tokens: self.cache_write_tokens + rhs.cache_write_tokens,
         }
     }
 }
@@ -384,6 +961,7 @@ impl std::ops::Add for CompletionTokenDetails {
     fn add(self, rhs: Self) -> Self::Output {
         Self {
             reasoning_tokens: self.reasoning_tokens + rhs.reasoning_tokens,
+            audio_tokens: self.audio_tokens + rhs.audio_tokens,
             accepted_prediction_tokens: self.accepted_prediction_tokens
                 + rhs.accepted_prediction_tokens,
             rejected_prediction_tokens: self.rejected_prediction_tokens
@@ -431,6 +1009,59 @@ pub enum ChatError {
     /// The API did not return any choices.
     #[error("No choices returned from API")]
     NoChoices,
+
+    /// The API responded with an unsuccessful HTTP status, and a body that could not be parsed.
+    #[error("API responded with HTTP status {status}: `{response}`")]
+    HttpError {
+        /// The HTTP status code.
+        status: u16,
+        /// The body of the response.
+        response: String,
+    },
+
+    /// The request was not sent, because it could have pushed spending past the budget (see [`ChatClient::with_budget`]).
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetExceeded),
+
+    /// The request was not sent, because the model doesn't support a feature it uses (see [`crate::models`]).
+    #[error(transparent)]
+    Unsupported(#[from] Unsupported),
+
+    /// The type passed to [`ChatClient::classify`] is not an enum whose variants are plain strings.
+    #[error("`{0}` can't be used as a classifier, because it is not a fieldless enum")]
+    NotAnEnum(String),
+}
+
+impl ChatError {
+    /// Whether the error is one that trying another model might fix.
+    fn should_fall_back(&self) -> bool {
+        match self {
+            ChatError::RequestError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status()
+                        .is_some_and(|status| status.is_server_error() || status.as_u16() == 429)
+            }
+            ChatError::HttpError { status, .. } => *status >= 500 || *status == 429,
+            ChatError::ApiError(error, _) => {
+                let code = error.code.as_deref().unwrap_or_default();
+                [
+                    "rate_limit",
+                    "insufficient_quota",
+                    "server_error",
+                    "api_error",
+                    "overloaded",
+                    "timeout",
+                ]
+                .iter()
+                .any(|kind| error.r#type.contains(kind) || code.contains(kind))
+            }
+            ChatError::ResponseNotConformantToSchema(_)
+            | ChatError::NoChoices
+            | ChatError::Unsupported(_) => true,
+            _ => false,
+        }
+    }
 }
 
 /// Errors that can occur when sending many chat requests via the batch API.
@@ -482,6 +1113,18 @@ pub enum BatchChatError {
     /// An error occurred when listing the batches.
     #[error("Error listing batches")]
     ListBatchesError(#[from] crate::batch::ListBatchesError),
+
+    /// The client's provider does not support the batch API.
+    #[error("The provider `{0}` does not support the batch API")]
+    BatchUnsupported(String),
+
+    /// The batch was not created, because it could have pushed spending past the budget (see [`ChatClient::with_budget`]).
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetExceeded),
+
+    /// The batch was not created, because the model doesn't support the batch API or a feature the requests use (see [`crate::models`]).
+    #[error(transparent)]
+    Unsupported(#[from] Unsupported),
 }
 
 /// Errors that can occur when sending many chat requests via the batch API.
@@ -519,6 +1162,266 @@ impl ChatClient {
             lru: RwLock::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
             usage: RwLock::new(ChatUsage::default()),
             cache_directory: None,
+            structured_output_mode: StructuredOutputMode::default(),
+            provider: Arc::new(OpenAiProvider),
+            fallbacks: Vec::new(),
+            ledger: Arc::new(UsageLedger::new()),
+            max_tokens: None,
+            reasoning_effort: None,
+            prediction: None,
+            logprobs: None,
+        }
+    }
+
+    /// Create a new [`ChatClient`] that talks to Anthropic's native [Messages API](https://docs.anthropic.com/en/api/messages) (using the [`AnthropicProvider`]).
+    /// If the API key is in the environment, you can use the [`Self::anthropic_from_env`] method instead.
+    ///
+    /// Unlike Anthropic's OpenAI-compatible endpoint, this supports the typed methods (such as [`Self::chat`]).
+    /// The batch methods are not supported.
+    ///
+    /// ```rust
+    /// use tysm::chat_completions::ChatClient;
+    ///
+    /// let client = ChatClient::anthropic("YOUR ANTHROPIC API KEY HERE", "claude-sonnet-4-20250514");
+    /// ```
+    pub fn anthropic(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            base_url: url::Url::parse("https://api.anthropic.com/v1/").unwrap(),
+            chat_completions_path: "messages".to_string(),
+            provider: Arc::new(AnthropicProvider::default()),
+            ..Self::new(api_key, model)
+        }
+    }
+
+    /// Create a new [`ChatClient`] that talks to Anthropic's native Messages API.
+    /// This will use the `ANTHROPIC_API_KEY` environment variable to set the API key.
+    /// It will also look in the `.env` file for an `ANTHROPIC_API_KEY` variable (using dotenv).
+    ///
+    /// ```rust,no_run
+    /// # use tysm::chat_completions::ChatClient;
+    /// let client = ChatClient::anthropic_from_env("claude-sonnet-4-20250514").unwrap();
+    /// ```
+    pub fn anthropic_from_env(model: impl Into<String>) -> Result<Self, AnthropicApiKeyError> {
+        Ok(Self::anthropic(anthropic_api_key()?, model))
+    }
+
+    /// Create a new [`ChatClient`] for [Azure OpenAI](https://learn.microsoft.com/en-us/azure/ai-services/openai/) (using the [`AzureProvider`]).
+    ///
+    /// `endpoint` is your resource's endpoint (such as `https://my-resource.openai.azure.com/`),
+    /// and `deployment` is the name of your model deployment. The deployment name is also used as the model name
+    /// (which matters for cost tracking).
+    ///
+    /// ```rust
+    /// use tysm::chat_completions::ChatClient;
+    ///
+    /// let client = ChatClient::azure("https://my-resource.openai.azure.com/", "gpt-4o", "2024-10-21", "YOUR AZURE API KEY");
+    /// ```
+    ///
+    /// Panics if the endpoint is not a valid URL.
+    pub fn azure(
+        endpoint: impl Into<String>,
+        deployment: impl Into<String>,
+        api_version: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Self {
+        let deployment = deployment.into();
+        Self {
+            base_url: base_url(endpoint),
+            chat_completions_path: format!("openai/deployments/{deployment}/chat/completions"),
+            provider: Arc::new(AzureProvider {
+                api_version: api_version.into(),
+            }),
+            ..Self::new(api_key, deployment)
+        }
+    }
+
+    /// Create a new [`ChatClient`] that talks to a local [Ollama](https://ollama.com) server through its native chat API.
+    ///
+    /// The batch methods are not supported.
+    ///
+    /// ```rust
+    /// use tysm::chat_completions::ChatClient;
+    ///
+    /// let client = ChatClient::ollama("llama3.2");
+    /// // if ollama isn't running on the default port:
+    /// let client = ChatClient::ollama("llama3.2").with_url("http://192.168.1.2:11434/");
+    /// ```
+    pub fn ollama(model: impl Into<String>) -> Self {
+        Self {
+            base_url: url::Url::parse("http://localhost:11434/").unwrap(),
+            chat_completions_path: "api/chat".to_string(),
+            provider: Arc::new(OllamaProvider),
+            ..Self::new("", model)
+        }
+    }
+
+    /// Set the provider that requests are sent through.
+    ///
+    /// ```rust
+    /// # use tysm::chat_completions::ChatClient;
+    /// use tysm::providers::AnthropicProvider;
+    ///
+    /// let client = ChatClient::new("YOUR ANTHROPIC API KEY HERE", "claude-sonnet-4-20250514")
+    ///     .with_url("https://api.anthropic.com/v1/")
+    ///     .with_provider(AnthropicProvider::default());
+    /// ```
+    ///
+    /// Remember to also set the URL and the [`Self::chat_completions_path`] to match the provider.
+    pub fn with_provider(self, provider: impl ChatProvider + 'static) -> Self {
+        Self {
+            provider: Arc::new(provider),
+            ..self
+        }
+    }
+
+    /// Set the models to fall back to when a request fails.
+    ///
+    /// When a request to the client's model fails with a server error, a rate limit, a refusal, or a response
+    /// that doesn't match the schema, the same request is retried with each fallback in order.
+    /// Use [`Self::chat_with_messages_detailed`] to find out which model ended up answering.
+    ///
+    /// ```rust
+    /// # use tysm::chat_completions::ChatClient;
+    /// let client = ChatClient::new("sk-1234567890", "gpt-4.1").with_fallbacks(["gpt-4o", "gpt-4o-mini"]);
+    /// ```
+    ///
+    /// The batch methods don't use fallbacks.
+    pub fn with_fallbacks(mut self, models: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.fallbacks.extend(
+            models
+                .into_iter()
+                .map(|model| Fallback::Model(model.into())),
+        );
+        self
+    }
+
+    /// Add a client to fall back to when a request fails. This is useful for falling back to a different provider.
+    ///
+    /// ```rust
+    /// # use tysm::chat_completions::ChatClient;
+    /// let client = ChatClient::new("sk-1234567890", "gpt-4.1")
+    ///     .with_fallback_client(ChatClient::anthropic("YOUR ANTHROPIC API KEY HERE", "claude-sonnet-4-20250514"));
+    /// ```
+    ///
+    /// See [`Self::with_fallbacks`] for when fallbacks are used. The usage of the fallback client's requests is recorded in this client.
+    pub fn with_fallback_client(mut self, client: ChatClient) -> Self {
+        self.fallbacks.push(Fallback::Client(Box::new(client)));
+        self
+    }
+
+    /// Record this client's usage in `ledger`, so that it can be shared with other clients.
+    ///
+    /// ```rust
+    /// # use std::sync::Arc;
+    /// # use tysm::chat_completions::ChatClient;
+    /// # use tysm::usage::UsageLedger;
+    /// let ledger = Arc::new(UsageLedger::new());
+    /// let gpt = ChatClient::new("sk-1234567890", "gpt-4.1").with_ledger(ledger.clone());
+    /// let claude = ChatClient::anthropic("YOUR ANTHROPIC API KEY HERE", "claude-sonnet-4-20250514")
+    ///     .with_ledger(ledger.clone());
+    /// ```
+    pub fn with_ledger(mut self, ledger: Arc<UsageLedger>) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    /// Use `prices` to compute the cost of the client's usage (see [`PriceTable`]).
+    ///
+    /// Like the budget, the prices are stored in the client's [ledger](Self::ledger), so call this after [`Self::with_ledger`].
+    pub fn with_prices(self, prices: PriceTable) -> Self {
+        self.ledger.set_prices(prices);
+        self
+    }
+
+    /// Refuse to send requests once they could push spending past `dollars`.
+    ///
+    /// The budget is stored in the client's [ledger](Self::ledger) (so call this after [`Self::with_ledger`]), and applies to
+    /// every client that shares the ledger, including embeddings and batch requests. See [`UsageLedger::set_budget`] for how it is enforced.
+    ///
+    /// ```rust
+    /// # use tysm::chat_completions::ChatClient;
+    /// let client = ChatClient::new("sk-1234567890", "gpt-4.1")
+    ///     .with_max_tokens(1000)
+    ///     .with_budget(5.0);
+    /// ```
+    pub fn with_budget(self, dollars: f64) -> Self {
+        self.ledger.set_budget(Some(dollars));
+        self
+    }
+
+    /// Set the maximum number of tokens the model may generate per request.
+    ///
+    /// Reasoning models (see [`ModelInfo::reasoning`](crate::models::ModelInfo::reasoning)) are sent this as `max_completion_tokens`
+    /// instead of `max_tokens`, and their reasoning tokens count towards it.
+    /// This also tightens the worst-case estimate used by [`Self::with_budget`].
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set how much reasoning models (such as `o3` and `o4-mini`) should think before answering.
+    ///
+    /// The effort is not sent to models that are known not to reason, so a fallback to such a model still works.
+    /// Requests to reasoning models that don't accept the `reasoning_effort` parameter (such as `o1-mini`) fail with
+    /// [`ChatError::Unsupported`] (so with fallbacks, the next model is tried).
+    ///
+    /// ```rust
+    /// use tysm::chat_completions::{ChatClient, ReasoningEffort};
+    ///
+    /// let client = ChatClient::new("sk-1234567890", "o4-mini").with_reasoning_effort(ReasoningEffort::High);
+    /// ```
+    pub fn with_reasoning_effort(mut self, reasoning_effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(reasoning_effort);
+        self
+    }
+
+    /// Send a [predicted output](https://platform.openai.com/docs/guides/predicted-outputs) with each request.
+    ///
+    /// When most of the response is known in advance (for example, when asking the model to make a small edit to a document),
+    /// the model can skip generating the parts that match the prediction, which makes the response much faster. Predicted tokens that
+    /// don't end up in the response are still billed (see [`CompletionTokenDetails::rejected_prediction_tokens`]).
+    ///
+    /// The prediction is part of the request, so it is part of the cache key, and it is sent with every request in a batch.
+    ///
+    /// ```rust
+    /// use tysm::chat_completions::ChatClient;
+    ///
+    /// let code = "fn main() {\n    println!(\"Hello, world!\");\n}\n";
+    /// let client = ChatClient::new("sk-1234567890", "gpt-4.1").with_prediction(code);
+    /// ```
+    pub fn with_prediction(mut self, content: impl Into<String>) -> Self {
+        self.prediction = Some(Prediction::Content {
+            content: content.into(),
+        });
+        self
+    }
+
+    /// Return the log probability of each token in the response, along with the `top_logprobs` most likely
+    /// alternatives at each position (up to 20, or 0 for none).
+    ///
+    /// They are returned in [`ChatCompletion::logprobs`] by the `*_detailed` methods. For typed responses,
+    /// [`ChatCompletion::field_confidence`] turns them into a confidence score for each field.
+    ///
+    /// Reasoning models don't return log probabilities, so requests to them fail with [`ChatError::Unsupported`].
+    pub fn with_logprobs(mut self, top_logprobs: u8) -> Self {
+        self.logprobs = Some(top_logprobs);
+        self
+    }
+
+    /// Set how the typed methods ask the model for structured output.
+    ///
+    /// Use this for providers that don't support `json_schema` response formats.
+    ///
+    /// ```rust
+    /// # use tysm::chat_completions::{ChatClient, StructuredOutputMode};
+    /// let client = ChatClient::new("YOUR API KEY", "claude-3-7-sonnet-20250219")
+    ///     .with_url("https://api.anthropic.com/v1/")
+    ///     .with_structured_output_mode(StructuredOutputMode::PromptOnly);
+    /// ```
+    pub fn with_structured_output_mode(self, structured_output_mode: StructuredOutputMode) -> Self {
+        Self {
+            structured_output_mode,
+            ..self
         }
     }
 
@@ -554,16 +1457,8 @@ impl ChatClient {
     ///
     /// Panics if the argument is not a valid URL.
     pub fn with_url(self, url: impl Into<String>) -> Self {
-        let url = url.into();
-        let url = if url.ends_with('/') {
-            url
-        } else {
-            format!("{}/", url)
-        };
-
-        let url = url::Url::parse(&url).unwrap();
         Self {
-            base_url: url,
+            base_url: base_url(url),
             ..self
         }
     }
@@ -696,12 +1591,14 @@ impl ChatClient {
     /// let response: CityName = client.chat_with_messages(vec![
     ///     ChatMessage {
     ///         role: Role::System,
+    ///         name: None,
     ///         content: vec![ChatMessageContent::Text {
     ///             text: "You are an expert on cities.".to_string(),
     ///         }],
     ///     },
     ///     ChatMessage {
     ///         role: Role::User,
+    ///         name: None,
     ///         content: vec![ChatMessageContent::Text {
     ///             text: "What is the capital of Portugal?".to_string(),
     ///         }],
@@ -716,19 +1613,55 @@ impl ChatClient {
         &self,
         messages: Vec<ChatMessage>,
     ) -> Result<T, ChatError> {
-        let json_schema = JsonSchemaFormat::new::<T>();
+        Ok(self.chat_with_messages_detailed(messages).await?.value)
+    }
 
-        let response_format = ResponseFormat::JsonSchema { json_schema };
+    /// Like [`Self::chat_with_messages`], but also reports which model produced the response, and how many tokens it used.
+    ///
+    /// This is mostly useful along with [`Self::with_fallbacks`].
+    pub async fn chat_with_messages_detailed<T: DeserializeOwned + JsonSchema>(
+        &self,
+        messages: Vec<ChatMessage>,
+    ) -> Result<ChatCompletion<T>, ChatError> {
+        self.complete_with_fallbacks(
+            messages,
+            |client, messages| client.structured_request::<T>(messages),
+            |_| {},
+            |response, strict| {
+                Self::decode_json(&response, strict).map_err(|e| {
+                    IndividualChatError::ResponseNotConformantToSchema(
+                        e,
+                        response.trim().to_string(),
+                    )
+                    .into()
+                })
+            },
+        )
+        .await
+    }
 
-        let chat_response = self
-            .chat_with_messages_raw(messages, response_format)
+    /// Like [`Self::chat_with_messages`], but also returns the response as it was sent by the model.
+    pub(crate) async fn chat_with_messages_keeping_raw<T: DeserializeOwned + JsonSchema>(
+        &self,
+        messages: Vec<ChatMessage>,
+    ) -> Result<(T, String), ChatError> {
+        let completion = self
+            .complete_with_fallbacks(
+                messages,
+                |client, messages| client.structured_request::<T>(messages),
+                |_| {},
+                |response, strict| {
+                    let value = Self::decode_json(&response, strict).map_err(|e| {
+                        IndividualChatError::ResponseNotConformantToSchema(
+                            e,
+                            response.trim().to_string(),
+                        )
+                    })?;
+                    Ok((value, response))
+                },
+            )
             .await?;
-
-        let chat_response: T = Self::decode_json(&chat_response).map_err(|e| {
-            IndividualChatError::ResponseNotConformantToSchema(e, chat_response.trim().to_string())
-        })?;
-
-        Ok(chat_response)
+        Ok(completion.value)
     }
 
     /// Send a sequence of chat messages to the API. It's called "chat_with_messages_raw" because it allows you to specify any response format, and doesn't attempt to deserialize the chat completion.
@@ -737,33 +1670,123 @@ impl ChatClient {
         messages: Vec<ChatMessage>,
         response_format: ResponseFormat,
     ) -> Result<String, ChatError> {
-        let chat_request = ChatRequest {
-            model: self.model.clone(),
+        Ok(self
+            .chat_with_messages_raw_detailed(messages, response_format)
+            .await?
+            .value)
+    }
+
+    /// Like [`Self::chat_with_messages_raw`], but also reports which model produced the response, and how many tokens it used.
+    pub async fn chat_with_messages_raw_detailed(
+        &self,
+        messages: Vec<ChatMessage>,
+        response_format: ResponseFormat,
+    ) -> Result<ChatCompletion<String>, ChatError> {
+        self.complete_with_fallbacks(
             messages,
-            response_format,
-        };
+            |_, messages| (messages, response_format.clone()),
+            |_| {},
+            |response, _| Ok(response),
+        )
+        .await
+    }
+
+    /// Try the primary model, and then each of the fallbacks in order, until one of them succeeds
+    /// (or fails with an error that a fallback wouldn't help with).
+    ///
+    /// `customize` can change the request before it is sent (for example, to set options that only apply to one method).
+    /// `decode` is also told whether the schema was enforced by the API (see [`Self::decode_json`]).
+    pub(crate) async fn complete_with_fallbacks<T>(
+        &self,
+        messages: Vec<ChatMessage>,
+        prepare: impl Fn(&ChatClient, Vec<ChatMessage>) -> (Vec<ChatMessage>, ResponseFormat),
+        customize: impl Fn(&mut ChatRequest),
+        decode: impl Fn(String, bool) -> Result<T, ChatError>,
+    ) -> Result<ChatCompletion<T>, ChatError> {
+        let attempts = std::iter::once((self, self.model.as_str())).chain(
+            self.fallbacks.iter().map(|fallback| match fallback {
+                Fallback::Model(model) => (self, model.as_str()),
+                Fallback::Client(client) => (client.as_ref(), client.model.as_str()),
+            }),
+        );
+        let mut attempts = attempts.peekable();
+
+        while let Some((client, model)) = attempts.next() {
+            let (messages, response_format) = prepare(client, messages.clone());
+            let strict = matches!(response_format, ResponseFormat::JsonSchema { .. });
+            let result = self
+                .complete(client, model, messages, response_format, &customize)
+                .await
+                .and_then(|completion| {
+                    let value = decode(completion.value, strict)?;
+                    Ok(ChatCompletion {
+                        value,
+                        model: completion.model,
+                        usage: completion.usage,
+                        cached: completion.cached,
+                        logprobs: completion.logprobs,
+                    })
+                });
+
+            match result {
+                Err(e) if e.should_fall_back() && attempts.peek().is_some() => {
+                    warn!("Request to `{model}` failed, trying the next fallback: {e}");
+                }
+                result => return result,
+            }
+        }
+
+        unreachable!("there is always at least one attempt")
+    }
+
+    /// Build the request for `model`, adapted to what the model expects (see [`crate::models`]).
+    fn request(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        response_format: ResponseFormat,
+    ) -> ChatRequest {
+        let info = models::info(model);
+        let reasoning = info.is_some_and(|info| info.reasoning);
+        // the effort is kept for models that aren't known, since they may well be reasoning models
+        let reasoning_effort = self
+            .reasoning_effort
+            .filter(|_| info.is_none_or(|info| info.reasoning));
+        ChatRequest {
+            max_tokens: self.max_tokens.filter(|_| !reasoning),
+            max_completion_tokens: self.max_tokens.filter(|_| reasoning),
+            reasoning_effort,
+            prediction: self.prediction.clone(),
+            logprobs: self.logprobs.map(|_| true),
+            top_logprobs: self.logprobs.filter(|&top_logprobs| top_logprobs > 0),
+            ..ChatRequest::new(model, models::adapt_roles(model, messages), response_format)
+        }
+    }
+
+    /// Send a request for `model` through `via` (which is either this client, or one of its fallback clients).
+    /// The request is built with `via`'s options (such as its `max_tokens`), but the usage is recorded in this client either way.
+    async fn complete(
+        &self,
+        via: &ChatClient,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        response_format: ResponseFormat,
+        customize: &dyn Fn(&mut ChatRequest),
+    ) -> Result<ChatCompletion<String>, ChatError> {
+        let mut chat_request = via.request(model, messages, response_format);
+        customize(&mut chat_request);
 
         let chat_request_str = serde_json::to_string(&chat_request).unwrap();
 
-        let chat_response = if let Some(cached_response) = self.chat_cached(&chat_request).await {
-            debug!("Using cached response: {cached_response}");
-            let chat_response: ChatResponseOrError = serde_json::from_str(&cached_response)
-                .map_err(|e| {
-                    let chat_request_str = if chat_request_str.len() > 100 {
-                        chat_request_str
-                            .chars()
-                            .take(100)
-                            .chain("...".chars())
-                            .collect()
-                    } else {
-                        chat_request_str.clone()
-                    };
-                    ChatError::ApiParseError {
-                        response: cached_response.clone(),
-                        error: e,
-                        request: chat_request_str,
-                    }
-                })?;
+        // a cached response that can't be parsed (for example, one written by an older version) is fetched again
+        let cached_response = via.chat_cached(&chat_request).await.and_then(|response| {
+            debug!("Using cached response: {response}");
+            via.parse_response(&response)
+                .inspect_err(|e| warn!("Ignoring a cached response that could not be parsed: {e}"))
+                .ok()
+        });
+        let cached = cached_response.is_some();
+        let chat_response = if let Some(chat_response) = cached_response {
             match chat_response {
                 ChatResponseOrError::Response(response) => response,
                 ChatResponseOrError::Error(error) => {
@@ -780,14 +1803,27 @@ impl ChatClient {
                 }
             }
         } else {
-            let chat_response = self.chat_uncached(&chat_request).await?;
+            chat_request.check_capabilities()?;
+            self.ledger
+                .check_budget(model, Endpoint::Chat, chat_request.worst_case_usage())?;
+            let (status, chat_response) = via.chat_uncached(&chat_request).await?;
             debug!("Got response from API: {chat_response}");
-            let chat_response: ChatResponseOrError =
-                serde_json::from_str(&chat_response).map_err(|e| ChatError::ApiParseError {
-                    response: chat_response.clone(),
-                    error: e,
-                    request: chat_request_str.clone(),
-                })?;
+            let chat_response: ChatResponseOrError = match via.parse_response(&chat_response) {
+                Ok(chat_response) => chat_response,
+                Err(_) if !status.is_success() => {
+                    return Err(ChatError::HttpError {
+                        status: status.as_u16(),
+                        response: chat_response,
+                    });
+                }
+                Err(e) => {
+                    return Err(ChatError::ApiParseError {
+                        response: chat_response.clone(),
+                        error: e,
+                        request: chat_request_str.clone(),
+                    })
+                }
+            };
             let chat_response = match chat_response {
                 ChatResponseOrError::Response(response) => response,
                 ChatResponseOrError::Error(error) => {
@@ -798,18 +1834,28 @@ impl ChatClient {
             if let Ok(mut usage) = self.usage.write() {
                 *usage += chat_response.usage;
             }
+            self.ledger
+                .record(model, Endpoint::Chat, chat_response.usage);
             chat_response
         };
-        let message = chat_response
+        let choice = chat_response
             .choices
             .first()
             .ok_or(ChatError::NoChoices)?
-            .message
             .clone();
 
-        let chat_response = message.content().map_err(IndividualChatError::Refusal)?;
-
-        Ok(chat_response)
+        let content = choice
+            .message
+            .content()
+            .map_err(IndividualChatError::Refusal)?;
+
+        Ok(ChatCompletion {
+            value: content,
+            model: model.to_string(),
+            usage: chat_response.usage,
+            cached,
+            logprobs: choice.logprobs,
+        })
     }
 
     /// Send chat messages to the batch API and deserialize the responses into the given type.
@@ -855,24 +1901,21 @@ impl ChatClient {
         &self,
         messages: Vec<Vec<ChatMessage>>,
     ) -> Result<Vec<Result<T, IndividualChatError>>, BatchChatError> {
-        let json_schema = JsonSchemaFormat::new::<T>();
-
-        let response_format = ResponseFormat::JsonSchema { json_schema };
-
         let chat_responses = self
             .batch_chat_with_messages_raw(
                 messages
                     .into_iter()
-                    .map(|m| (m, response_format.clone()))
+                    .map(|m| self.structured_request::<T>(m))
                     .collect(),
             )
             .await?;
 
+        let strict = self.structured_output_mode == StructuredOutputMode::StrictJsonSchema;
         let chat_responses: Vec<Result<T, _>> = chat_responses
             .into_iter()
             .map(|chat_response| {
                 let chat_response = chat_response?;
-                Self::decode_json(&chat_response).map_err(|e| {
+                Self::decode_json(&chat_response, strict).map_err(|e| {
                     IndividualChatError::ResponseNotConformantToSchema(
                         e,
                         chat_response.trim().to_string(),
@@ -887,37 +1930,43 @@ impl ChatClient {
     /// Send a batch of sequences of chat messages to the API. It's called "chat_with_messages_raw" because it allows you to specify any response format, and doesn't attempt to deserialize the chat completion.
     ///
     /// This goes through the batch API, which is cheaper and has higher ratelimits, but is much higher-latency. The responses to the batch API stick around in OpenAI's servers for some time, and before starting a new batch request, `tysm` will automatically check if that same request has been made before (and reuse it if so).
+    ///
+    /// Requests are identified by a hash of everything that is sent (including options such as `max_tokens`). Since 0.9.0 the hash
+    /// is computed from the serialized request, so batches created by earlier versions are not reused.
     pub async fn batch_chat_with_messages_raw(
         &self,
         prompts: Vec<(Vec<ChatMessage>, ResponseFormat)>,
     ) -> Result<Vec<Result<String, IndividualChatError>>, BatchChatError> {
         use crate::batch::{BatchClient, BatchRequestItem};
 
+        if !self.provider.supports_batch() {
+            return Err(BatchChatError::BatchUnsupported(format!(
+                "{:?}",
+                self.provider
+            )));
+        }
+
+        models::require(&self.model, Capability::Batch)?;
+
         info!("Starting batch chat with {} prompts", prompts.len());
 
         let batch_client = BatchClient::from(self);
 
+        let mut worst_case = ChatUsage::default();
         let (custom_ids, requests) = prompts
             .into_iter()
             .map(|(messages, response_format)| {
-                let request_str = format!("{messages:?}, {response_format:?}, {:?}", self.model);
-                let request_hash = const_xxh3(request_str.as_bytes());
+                let chat_request = self.request(&self.model, messages, response_format);
+                let request_hash = chat_request.batch_hash();
                 let custom_id = format!("request-{}", request_hash);
-                (
-                    (custom_id.clone(), request_hash),
-                    (
-                        request_hash,
-                        BatchRequestItem::new_chat(
-                            custom_id,
-                            ChatRequest {
-                                model: self.model.clone(),
-                                messages,
-                                response_format,
-                            },
-                        ),
-                    ),
-                )
+                chat_request.check_capabilities()?;
+                worst_case += chat_request.worst_case_usage();
+                let mut request = BatchRequestItem::new_chat(custom_id.clone(), chat_request);
+                request.url = batch_client.endpoint.clone();
+                Ok(((custom_id, request_hash), (request_hash, request)))
             })
+            .collect::<Result<Vec<_>, BatchChatError>>()?
+            .into_iter()
             .unzip::<_, _, Vec<_>, HashMap<_, _>>();
         let requests = requests.values().cloned().collect::<Vec<_>>();
 
@@ -954,11 +2003,14 @@ impl ChatClient {
             .cloned();
 
         // If the batch already exists, use it. Otherwise, create a new one.
+        let reused = batch.is_some();
         let batch = if let Some(batch) = batch {
             info!("Reusing existing batch");
             batch
         } else {
             info!("No batch with matching hash found found, creating a new one");
+            self.ledger
+                .check_budget(&self.model, Endpoint::Batch, worst_case)?;
             // Create the batch content
             let content = batch_client.create_batch_content(&requests);
 
@@ -1018,6 +2070,14 @@ impl ChatClient {
             })
             .collect::<Result<HashMap<_, _>, BatchChatError>>()?;
 
+        // A reused batch was already paid for (and recorded) when it was created.
+        if !reused {
+            for response in results.values() {
+                self.ledger
+                    .record(&response.model, Endpoint::Batch, response.usage);
+            }
+        }
+
         let results = custom_ids
             .into_iter()
             .map(|custom_id| {
@@ -1043,8 +2103,23 @@ impl ChatClient {
         Ok(results)
     }
 
+    /// The name of the file a response to `chat_request` is cached in.
+    ///
+    /// Responses are in the provider's own format, so the provider and the URL are part of the key: clients for
+    /// different APIs can share a cache directory without reading each other's responses.
+    fn cache_key(&self, chat_request: &ChatRequest) -> String {
+        let serialized = serde_json::to_string(chat_request).unwrap();
+        let scoped = format!(
+            "{:?}\n{}\n{serialized}",
+            self.provider,
+            self.chat_completions_url()
+        );
+        let id = const_xxh3(scoped.as_bytes());
+        format!("tysm-v2-chat_request-{}.zstd", id)
+    }
+
     async fn chat_cached(&self, chat_request: &ChatRequest) -> Option<String> {
-        let chat_request_cache_key = chat_request.cache_key();
+        let chat_request_cache_key = self.cache_key(chat_request);
         let chat_request = serde_json::to_string(chat_request).ok()?;
 
         // First, check the lru (which we just peek so it's not even really used as a LRU)
@@ -1078,22 +2153,31 @@ impl ChatClient {
         Some(response)
     }
 
-    async fn chat_uncached(&self, chat_request: &ChatRequest) -> Result<String, ChatError> {
+    async fn chat_uncached(
+        &self,
+        chat_request: &ChatRequest,
+    ) -> Result<(reqwest::StatusCode, String), ChatError> {
         let reqwest_client = Client::new();
 
-        let response = reqwest_client
-            .post(self.chat_completions_url())
-            .header("Authorization", format!("Bearer {}", self.api_key.clone()))
-            .header("Content-Type", "application/json")
-            .json(chat_request)
+        let response = self
+            .provider
+            .build_request(
+                &reqwest_client,
+                self.chat_completions_url(),
+                &self.api_key,
+                chat_request,
+            )
             .send()
-            .await?
-            .text()
             .await?;
+        let status = response.status();
+        let response = response.text().await?;
 
         // simple heuristic to avoid caching errors
-        if !response.starts_with("{\"error\":") && !response.starts_with("error code") {
-            let chat_request_cache_key = chat_request.cache_key();
+        if status.is_success()
+            && !response.starts_with("{\"error\":")
+            && !response.starts_with("error code")
+        {
+            let chat_request_cache_key = self.cache_key(chat_request);
             let chat_request = serde_json::to_string(chat_request)
                 .map_err(|e| ChatError::JsonSerializeError(e, chat_request.clone()))?;
 
@@ -1116,26 +2200,70 @@ impl ChatClient {
             }
         }
 
-        Ok(response)
+        Ok((status, response))
     }
 
-    fn decode_json<T: DeserializeOwned>(json: &str) -> Result<T, serde_json::Error> {
-        match serde_json::from_str(json) {
-            Ok(chat_response) => Ok(chat_response),
-            Err(e) => {
-                // try decoding each line separately
-                {
-                    let lines = json.lines();
-                    for line in lines {
-                        if let Ok(chat_response) = serde_json::from_str(line) {
-                            return Ok(chat_response);
-                        }
-                    }
-                }
+    /// Parse a response body from the API into the OpenAI response structure.
+    fn parse_response(&self, response: &str) -> Result<ChatResponseOrError, serde_json::Error> {
+        self.provider.parse_response(response)
+    }
+
+    /// Prepare the messages and response format for a typed request, according to [`Self::structured_output_mode`].
+    pub(crate) fn structured_request<T: JsonSchema>(
+        &self,
+        mut messages: Vec<ChatMessage>,
+    ) -> (Vec<ChatMessage>, ResponseFormat) {
+        let json_schema = JsonSchemaFormat::new::<T>();
+
+        let response_format = match self.structured_output_mode {
+            StructuredOutputMode::StrictJsonSchema => {
+                return (messages, ResponseFormat::JsonSchema { json_schema })
+            }
+            StructuredOutputMode::JsonObject => ResponseFormat::JsonObject,
+            StructuredOutputMode::PromptOnly => ResponseFormat::Text,
+        };
 
-                // give up
-                Err(e)
+        let schema = serde_json::to_string_pretty(&json_schema.schema).unwrap();
+        let instructions = format!(
+            "Respond only with a JSON value that conforms to the following JSON schema. Do not include any other text.\n```json\n{schema}\n```"
+        );
+
+        match messages.first_mut() {
+            Some(ChatMessage {
+                role: Role::System | Role::Developer,
+                content,
+                ..
+            }) => {
+                // `chat` and `batch_chat` send an empty system prompt, which we can just replace
+                let is_empty = content.iter().all(|part| {
+                    matches!(part, ChatMessageContent::Text { text } if text.trim().is_empty())
+                });
+                if is_empty {
+                    content.clear();
+                    content.push(ChatMessageContent::Text { text: instructions });
+                } else {
+                    content.push(ChatMessageContent::Text {
+                        text: format!("\n\n{instructions}"),
+                    });
+                }
             }
+            _ => messages.insert(0, ChatMessage::system(instructions)),
+        }
+
+        (messages, response_format)
+    }
+
+    /// Deserialize a typed response. If the schema was enforced by the API (`strict`), the response is parsed as it is,
+    /// so that a response that doesn't match the schema is reported rather than matched against the JSON inside of it.
+    /// Otherwise, the JSON is extracted from the response with [`extract_json`](crate::extract::extract_json).
+    pub(crate) fn decode_json<T: DeserializeOwned>(
+        json: &str,
+        strict: bool,
+    ) -> Result<T, serde_json::Error> {
+        if strict {
+            serde_json::from_str(json)
+        } else {
+            crate::extract::extract_json(json)
         }
     }
 
@@ -1148,13 +2276,23 @@ impl ChatClient {
 
     /// Attempts to compute the cost in dollars of the usage of this client.
     ///
-    /// This is provided on a best-effort basis. The prices are hardcoded into
+    /// This is provided on a best-effort basis. The default prices are hardcoded into
     /// the library (as OpenAI doesn't provide an API to get API pricing info),
-    /// and may be out of date or unavailable for the model you're using.
+    /// and may be out of date or unavailable for the model you're using (see [`Self::with_prices`]).
     /// If you notice the prices being out of date, [please leave an issue](https://github.com/not-pizza/tysm)!
+    ///
+    /// This is the cost of everything in the client's [ledger](Self::ledger): each model's usage is priced separately,
+    /// and batch requests are priced at the batch discount. If the ledger is shared with other clients, their usage is included too.
+    /// If the price of any of the models is unknown, this returns `None`.
     pub fn cost(&self) -> Option<f64> {
-        let usage = self.usage();
-        crate::model_prices::cost(&self.model, usage)
+        self.ledger.cost()
+    }
+
+    /// Returns how many tokens have been used so far, broken down by model (including batch requests).
+    ///
+    /// Does not double-count tokens used in cached responses.
+    pub fn usage_by_model(&self) -> HashMap<String, ChatUsage> {
+        self.ledger.usage_by_model()
     }
 }
 
@@ -1185,5 +2323,344 @@ fn test_deser() {
     }
 }
 "#;
-    let _chat_response: ChatResponse = serde_json::from_str(&s).unwrap();
+    let _chat_response: ChatResponse = serde_json::from_str(s).unwrap();
+}
+
+#[test]
+fn test_usage_details_deser() {
+    let usage: ChatUsage = serde_json::from_str(
+        r#"{
+            "prompt_tokens": 1117,
+            "completion_tokens": 46,
+            "total_tokens": 1163,
+            "prompt_tokens_details": {"cached_tokens": 1024, "audio_tokens": 0},
+            "completion_tokens_details": {"reasoning_tokens": 32, "audio_tokens": 0}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(usage.prompt_token_details.unwrap().cached_tokens, 1024);
+    assert_eq!(usage.completion_token_details.unwrap().reasoning_tokens, 32);
+}
+
+#[test]
+fn test_prompt_only_mode() {
+    #[derive(Deserialize, JsonSchema, Debug, PartialEq)]
+    struct Name {
+        first: String,
+        last: String,
+    }
+
+    let client = ChatClient::new("sk-1234567890", "claude-3-7-sonnet-20250219")
+        .with_structured_output_mode(StructuredOutputMode::PromptOnly);
+    let (messages, response_format) = client.structured_request::<Name>(vec![
+        ChatMessage::system(""),
+        ChatMessage::user("Who was the first US president?"),
+    ]);
+    assert!(matches!(response_format, ResponseFormat::Text));
+    assert_eq!(messages.len(), 2);
+    match &messages[0].content[..] {
+        [ChatMessageContent::Text { text }] => assert!(text.contains("\"first\"")),
+        content => panic!("Expected the schema in the system prompt, got {content:?}"),
+    }
+
+    let reply =
+        "Sure! Here you go:\n```json\n{\"first\": \"George\", \"last\": \"Washington\"}\n```";
+    let name: Name = ChatClient::decode_json(reply, false).unwrap();
+    assert_eq!(
+        name,
+        Name {
+            first: "George".to_string(),
+            last: "Washington".to_string()
+        }
+    );
+
+    // when the schema is enforced, a mismatch isn't papered over by JSON found inside the response
+    #[derive(serde::Deserialize, Debug)]
+    struct Partial {
+        #[expect(unused)]
+        first: Option<String>,
+    }
+    let reply = r#"[{"first": "George"}]"#;
+    assert!(ChatClient::decode_json::<Partial>(reply, false).is_ok());
+    assert!(ChatClient::decode_json::<Partial>(reply, true).is_err());
+}
+
+#[test]
+fn test_reasoning_requests() {
+    let client = ChatClient::new("sk-1234567890", "o3").with_max_tokens(1000);
+    let request = client.request("o3", vec![ChatMessage::user("Hi")], ResponseFormat::Text);
+    let json = serde_json::to_value(&request).unwrap();
+    assert_eq!(json["max_completion_tokens"], 1000);
+    assert!(json.get("max_tokens").is_none());
+    assert!(json.get("reasoning_effort").is_none());
+
+    let client = client.with_reasoning_effort(ReasoningEffort::Low);
+    let request = client.request("o3", vec![ChatMessage::user("Hi")], ResponseFormat::Text);
+    assert_eq!(
+        serde_json::to_value(&request).unwrap()["reasoning_effort"],
+        "low"
+    );
+    assert!(request.check_capabilities().is_ok());
+
+    // a fallback to a model that doesn't reason
+    let client = client.with_fallbacks(["gpt-4o"]);
+    let request = client.request(
+        "gpt-4o",
+        vec![ChatMessage::user("Hi")],
+        ResponseFormat::Text,
+    );
+    let json = serde_json::to_value(&request).unwrap();
+    assert!(json.get("reasoning_effort").is_none());
+    assert!(json.get("max_completion_tokens").is_none());
+    assert_eq!(json["max_tokens"], 1000);
+    assert!(request.check_capabilities().is_ok());
+
+    let request = client.request(
+        "o1-mini",
+        vec![ChatMessage::user("Hi")],
+        ResponseFormat::Text,
+    );
+    assert!(matches!(
+        request.check_capabilities(),
+        Err(Unsupported {
+            capability: Capability::ReasoningEffort,
+            ..
+        })
+    ));
+
+    let client = ChatClient::new("sk-1234567890", "o3").with_logprobs(5);
+    let request = client.request("o3", vec![ChatMessage::user("Hi")], ResponseFormat::Text);
+    assert!(matches!(
+        request.check_capabilities(),
+        Err(Unsupported {
+            capability: Capability::Logprobs,
+            ..
+        })
+    ));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_cache_is_scoped_to_provider() {
+    let openai = ChatClient::new("sk-1234567890", "llama3.2").with_url("http://127.0.0.1:1/");
+    let ollama = ChatClient::ollama("llama3.2").with_url("http://127.0.0.1:1/");
+    let request = openai.request(
+        "llama3.2",
+        vec![ChatMessage::user("Hi")],
+        ResponseFormat::Text,
+    );
+    assert_ne!(openai.cache_key(&request), ollama.cache_key(&request));
+    let elsewhere = ChatClient::new("sk-1234567890", "llama3.2").with_url("http://127.0.0.1:2/");
+    assert_ne!(openai.cache_key(&request), elsewhere.cache_key(&request));
+
+    // a cached response that can't be parsed is sent again (and fails here, since nothing listens on the URL)
+    openai.lru.write().unwrap().put(
+        serde_json::to_string(&request).unwrap(),
+        r#"{"message": {"role": "assistant", "content": "from ollama"}}"#.to_string(),
+    );
+    let result = openai
+        .chat_with_messages_raw(vec![ChatMessage::user("Hi")], ResponseFormat::Text)
+        .await;
+    assert!(matches!(result, Err(ChatError::RequestError(_))));
+}
+
+#[test]
+fn test_batch_hash() {
+    let client = ChatClient::new("sk-1234567890", "gpt-4o");
+    let request = client.request(
+        "gpt-4o",
+        vec![ChatMessage::user("Hi")],
+        ResponseFormat::Text,
+    );
+    // fields that aren't sent (such as unset names) don't change the hash
+    assert_eq!(
+        request.batch_hash(),
+        const_xxh3(
+            r#"{"model":"gpt-4o","messages":[{"role":"user","content":[{"type":"text","text":"Hi"}]}],"response_format":{"type":"text"}}"#
+                .as_bytes()
+        )
+    );
+    let named = client.request(
+        "gpt-4o",
+        vec![ChatMessage::user("Hi").with_name("alice")],
+        ResponseFormat::Text,
+    );
+    assert_ne!(request.batch_hash(), named.batch_hash());
+}
+
+#[test]
+fn test_batch_hash_includes_max_tokens() {
+    let messages = vec![ChatMessage::user("Hi")];
+    let hash = |client: ChatClient| {
+        client
+            .request("gpt-4o", messages.clone(), ResponseFormat::Text)
+            .batch_hash()
+    };
+    let limited = hash(ChatClient::new("sk-1234567890", "gpt-4o").with_max_tokens(100));
+    assert_ne!(hash(ChatClient::new("sk-1234567890", "gpt-4o")), limited);
+    assert_ne!(
+        hash(ChatClient::new("sk-1234567890", "gpt-4o").with_max_tokens(1000)),
+        limited
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_fallback_client_options() {
+    let fallback = ChatClient::new("sk-1234567890", "gpt-4o-mini").with_max_tokens(50);
+    let messages = vec![ChatMessage::user("Hi")];
+    let expected = fallback.request("gpt-4o-mini", messages.clone(), ResponseFormat::Text);
+    let response = r#"{
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1714696172,
+        "model": "gpt-4o-mini",
+        "system_fingerprint": null,
+        "choices": [{"index": 0, "finish_reason": "stop", "logprobs": null, "message": {"role": "assistant", "content": "Hello!"}}],
+        "usage": {"prompt_tokens": 8, "completion_tokens": 2, "total_tokens": 10}
+    }"#;
+    fallback.lru.write().unwrap().put(
+        serde_json::to_string(&expected).unwrap(),
+        response.to_string(),
+    );
+
+    // nothing listens on the primary's URL, so the request falls back
+    let primary = ChatClient::new("sk-1234567890", "o3")
+        .with_url("http://127.0.0.1:1/")
+        .with_max_tokens(1000)
+        .with_reasoning_effort(ReasoningEffort::High)
+        .with_prediction("Hello!")
+        .with_fallback_client(fallback);
+    let completion = primary
+        .chat_with_messages_raw_detailed(messages, ResponseFormat::Text)
+        .await
+        .unwrap();
+    assert_eq!(completion.value, "Hello!");
+    assert_eq!(completion.model, "gpt-4o-mini");
+    assert!(completion.cached);
+}
+
+#[test]
+fn test_prediction() {
+    let client = ChatClient::new("sk-1234567890", "gpt-4.1");
+    let messages = vec![ChatMessage::user("Rename `x` to `count`.")];
+    let plain = client.request("gpt-4.1", messages.clone(), ResponseFormat::Text);
+
+    let client = client.with_prediction("let x = 1;");
+    let predicted = client.request("gpt-4.1", messages, ResponseFormat::Text);
+    assert_eq!(
+        serde_json::to_value(&predicted).unwrap()["prediction"],
+        serde_json::json!({"type": "content", "content": "let x = 1;"})
+    );
+    assert_ne!(client.cache_key(&plain), client.cache_key(&predicted));
+    let item = crate::batch::BatchRequestItem::new_chat("request-1", predicted);
+    assert_eq!(item.body["prediction"]["content"], "let x = 1;");
+}
+
+#[test]
+fn test_should_fall_back() {
+    let api_error = |r#type: &str, code: Option<&str>| {
+        ChatError::ApiError(
+            OpenAiError {
+                r#type: r#type.to_string(),
+                code: code.map(str::to_string),
+                message: "message".to_string(),
+                param: None,
+            },
+            "request".to_string(),
+        )
+    };
+
+    assert!(api_error("requests", Some("rate_limit_exceeded")).should_fall_back());
+    assert!(api_error("overloaded_error", None).should_fall_back());
+    assert!(api_error("server_error", None).should_fall_back());
+    assert!(!api_error("invalid_request_error", Some("invalid_api_key")).should_fall_back());
+
+    assert!(ChatError::HttpError {
+        status: 502,
+        response: "Bad Gateway".to_string()
+    }
+    .should_fall_back());
+    assert!(!ChatError::HttpError {
+        status: 404,
+        response: "Not Found".to_string()
+    }
+    .should_fall_back());
+    assert!(
+        ChatError::ResponseNotConformantToSchema(IndividualChatError::Refusal(
+            "I can't help with that.".to_string()
+        ))
+        .should_fall_back()
+    );
+}
+
+#[test]
+fn test_image_url_from_bytes() {
+    use base64::Engine;
+
+    let png = base64::engine::general_purpose::STANDARD
+        .decode("iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNk+A8AAQUBAScY42YAAAAASUVORK5CYII=")
+        .unwrap();
+    let image = ImageUrl::from_bytes(&png).unwrap();
+    assert!(image.url.starts_with("data:image/png;base64,iVBORw0KGgo"));
+    assert!(matches!(
+        ImageUrl::from_bytes(b"not an image"),
+        Err(ImageError::UnsupportedFormat)
+    ));
+
+    // the detail is only sent when it's set, so existing cache keys don't change
+    let message = ChatMessage::user_with_images("What's this?", [image.clone()]);
+    let json = serde_json::to_value(&message).unwrap();
+    assert_eq!(json["content"][1]["type"], "image_url");
+    assert!(json["content"][1]["image_url"].get("detail").is_none());
+    let json = serde_json::to_value(image.with_detail(ImageDetail::Low)).unwrap();
+    assert_eq!(json["detail"], "low");
+}
+
+#[test]
+fn test_file_and_audio_parts() {
+    let file = FileContent::from_bytes("report.pdf", b"%PDF-1.7");
+    assert_eq!(
+        file.file_data.as_deref(),
+        Some("data:application/pdf;base64,JVBERi0xLjc=")
+    );
+    let message =
+        ChatMessage::user_with_files("Summarize this.", [file, FileContent::from_id("file-abc")]);
+    let json = serde_json::to_value(&message).unwrap();
+    assert_eq!(json["content"][1]["type"], "file");
+    assert_eq!(json["content"][1]["file"]["filename"], "report.pdf");
+    assert_eq!(
+        json["content"][2],
+        serde_json::json!({"type": "file", "file": {"file_id": "file-abc"}})
+    );
+
+    let audio = ChatMessageContent::audio(InputAudio::from_bytes(b"RIFF", AudioFormat::Wav));
+    assert_eq!(
+        serde_json::to_value(&audio).unwrap(),
+        serde_json::json!({"type": "input_audio", "input_audio": {"data": "UklGRg==", "format": "wav"}})
+    );
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_image_downscale() {
+    use base64::Engine;
+
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::new_rgb8(100, 50)
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let image = ImageUrl::from_bytes(&png.into_inner())
+        .unwrap()
+        .with_detail(ImageDetail::High)
+        .downscale(20)
+        .unwrap();
+    assert_eq!(image.detail, Some(ImageDetail::High));
+
+    let (_, data) = image.url.split_once(";base64,").unwrap();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .unwrap();
+    let resized = image::load_from_memory(&bytes).unwrap();
+    assert_eq!((resized.width(), resized.height()), (20, 10));
 }
@@ -0,0 +1,228 @@
+//! Classifying text into the variants of an enum, with a probability for each label.
+//!
+//! ```rust,no_run
+//! # use tysm::chat_completions::ChatClient;
+//! # tokio_test::block_on(async {
+//! #[derive(serde::Deserialize, schemars::JsonSchema, Debug)]
+//! enum Sentiment {
+//!     Positive,
+//!     Neutral,
+//!     Negative,
+//! }
+//!
+//! let client = ChatClient::from_env("gpt-4o").unwrap();
+//! let (sentiment, probabilities) = client.classify::<Sentiment>("I love it!").await.unwrap();
+//! println!("{sentiment:?} ({:.1}% sure)", probabilities["Positive"] * 100.0);
+//! # })
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use schemars::{schema_for, JsonSchema};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::chat_completions::{
+    ChatClient, ChatError, ChatMessage, ChatRequest, IndividualChatError, ResponseFormat,
+};
+use crate::models::{self, Capability};
+
+/// The instructions given to the model.
+const CLASSIFIER_PROMPT: &str =
+    "Classify the user's message. Respond with the label that describes it best.";
+
+/// How many alternatives are requested for each token. This is the most the API allows.
+const TOP_LOGPROBS: u8 = 20;
+
+/// How much the first token of each label is favored when the schema isn't enforced by the API.
+const LABEL_BIAS: i32 = 5;
+
+/// The response, which is wrapped in an object because Structured Outputs requires one.
+#[derive(Deserialize, JsonSchema)]
+struct Label<E> {
+    label: E,
+}
+
+impl ChatClient {
+    /// Classify `text` into one of the variants of the fieldless enum `E`.
+    ///
+    /// Along with the label, this returns how likely the model thought each label was (keyed by the labels as they are
+    /// serialized, which sum to 1). The probabilities are computed from the log probabilities of the response's tokens
+    /// (see [`LogProbs::value_probabilities`](crate::logprobs::LogProbs::value_probabilities)). If the provider doesn't
+    /// return log probabilities, the chosen label gets a probability of 1. Models that are known not to return them
+    /// (such as reasoning models and Claude) aren't asked for them.
+    ///
+    /// If the schema isn't enforced by the API (see [`StructuredOutputMode`](crate::chat_completions::StructuredOutputMode)),
+    /// the first token of each label is favored with a logit bias, so that the model is less likely to respond with
+    /// something else. Only the first tokens are biased, and all of them equally, so the bias doesn't change how likely
+    /// the labels are relative to each other (unless a label's first token is also common elsewhere in the response).
+    pub async fn classify<E: DeserializeOwned + JsonSchema>(
+        &self,
+        text: impl Into<String>,
+    ) -> Result<(E, HashMap<String, f64>), ChatError> {
+        let labels = enum_labels(&serde_json::to_value(schema_for!(E)).unwrap())
+            .ok_or_else(|| ChatError::NotAnEnum(E::schema_name().to_string()))?;
+
+        let messages = vec![
+            ChatMessage::system(CLASSIFIER_PROMPT),
+            ChatMessage::user(text),
+        ];
+        let completion = self
+            .complete_with_fallbacks(
+                messages,
+                |client, messages| client.structured_request::<Label<E>>(messages),
+                |request| customize_request(request, &labels),
+                |response, strict| {
+                    let decode = |response: &str| {
+                        Self::decode_json::<Label<E>>(response, strict).map_err(|e| {
+                            IndividualChatError::ResponseNotConformantToSchema(
+                                e,
+                                response.trim().to_string(),
+                            )
+                        })
+                    };
+                    let chosen = Self::decode_json::<Label<String>>(&response, strict)
+                        .map(|label| label.label)
+                        .unwrap_or_default();
+                    Ok((decode(&response)?.label, chosen))
+                },
+            )
+            .await?;
+        let (label, chosen) = completion.value;
+
+        let probabilities = completion
+            .logprobs
+            .as_ref()
+            .and_then(|logprobs| {
+                logprobs.value_probabilities("/label", labels.iter().map(String::as_str))
+            })
+            .filter(|probabilities| probabilities.values().sum::<f64>() > 0.0)
+            .unwrap_or_else(|| {
+                labels
+                    .iter()
+                    .map(|label| (label.clone(), if *label == chosen { 1.0 } else { 0.0 }))
+                    .collect()
+            });
+        let total = probabilities.values().sum::<f64>();
+        let probabilities = probabilities
+            .into_iter()
+            .map(|(label, probability)| (label, probability / total))
+            .collect();
+
+        Ok((label, probabilities))
+    }
+}
+
+/// Ask for the log probabilities of the response's tokens (if the model returns them), and bias the labels' tokens
+/// if the schema isn't enforced by the API.
+fn customize_request(request: &mut ChatRequest, labels: &[String]) {
+    // models that are known not to return log probabilities are still asked for the label, which then gets a probability of 1
+    if models::require(&request.model, Capability::Logprobs).is_ok() {
+        request.logprobs = Some(true);
+        request.top_logprobs = Some(TOP_LOGPROBS);
+    }
+    if !matches!(request.response_format, ResponseFormat::JsonSchema { .. }) {
+        let bias = labels
+            .iter()
+            .filter_map(|label| {
+                crate::tokens::encode(&request.model, label)
+                    .first()
+                    .copied()
+            })
+            .map(|token| (token, LABEL_BIAS))
+            .collect::<BTreeMap<_, _>>();
+        request.logit_bias = Some(bias);
+    }
+}
+
+/// The labels of a fieldless enum's schema, or `None` if the schema isn't one.
+///
+/// Depending on whether the variants have doc comments, the labels are either in `enum`, or in the `const`s of `oneOf`.
+fn enum_labels(schema: &Value) -> Option<Vec<String>> {
+    let strings = |values: &Vec<Value>| {
+        values
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+    };
+    let labels = if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        strings(values)?
+    } else {
+        let variants = schema.get("oneOf")?.as_array()?;
+        let mut labels = Vec::new();
+        for variant in variants {
+            match (variant.get("const"), variant.get("enum")) {
+                (Some(Value::String(label)), _) => labels.push(label.clone()),
+                (_, Some(Value::Array(values))) => labels.extend(strings(values)?),
+                _ => return None,
+            }
+        }
+        labels
+    };
+    (!labels.is_empty()).then_some(labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_from_schema() {
+        #[derive(JsonSchema)]
+        #[expect(unused)]
+        enum Plain {
+            Positive,
+            Negative,
+        }
+
+        #[derive(JsonSchema)]
+        #[expect(unused)]
+        #[serde(rename_all = "lowercase")]
+        enum Documented {
+            /// Good
+            Positive,
+            /// Bad
+            Negative,
+        }
+
+        #[derive(JsonSchema)]
+        #[expect(unused)]
+        enum WithFields {
+            Score(u8),
+        }
+
+        let labels = |schema: schemars::Schema| enum_labels(&serde_json::to_value(schema).unwrap());
+        assert_eq!(
+            labels(schema_for!(Plain)),
+            Some(vec!["Positive".to_string(), "Negative".to_string()])
+        );
+        assert_eq!(
+            labels(schema_for!(Documented)),
+            Some(vec!["positive".to_string(), "negative".to_string()])
+        );
+        assert_eq!(labels(schema_for!(WithFields)), None);
+    }
+
+    #[test]
+    fn only_asks_models_that_return_logprobs() {
+        let labels = vec!["Positive".to_string(), "Negative".to_string()];
+        let build = |model: &str| {
+            let client = ChatClient::new("sk-1234567890", model);
+            let (messages, response_format) =
+                client.structured_request::<Label<String>>(vec![ChatMessage::user("I love it!")]);
+            let mut request = ChatRequest::new(model, messages, response_format);
+            customize_request(&mut request, &labels);
+            request
+        };
+
+        let request = build("claude-sonnet-4-20250514");
+        assert_eq!(request.logprobs, None);
+        assert!(request.check_capabilities().is_ok());
+
+        let request = build("gpt-4o");
+        assert_eq!(request.logprobs, Some(true));
+        assert_eq!(request.top_logprobs, Some(TOP_LOGPROBS));
+        assert!(request.check_capabilities().is_ok());
+    }
+}
@@ -0,0 +1,344 @@
+//! Long-running conversations that stay within the model's context window.
+//!
+//! A [`Conversation`] holds a system prompt and the history of a conversation. Before each request,
+//! [`ChatClient::chat_with_conversation`] drops the oldest turns until the conversation fits in the model's context window
+//! (see [`crate::models`]), always keeping the system prompt and the newest message. With a summarizer, the dropped turns
+//! are summarized by a (cheaper) model instead of being forgotten.
+//!
+//! ```rust,no_run
+//! # use tysm::chat_completions::{ChatClient, ChatMessage};
+//! # use tysm::conversation::Conversation;
+//! # tokio_test::block_on(async {
+//! let client = ChatClient::from_env("gpt-4o").unwrap();
+//! let mut conversation = Conversation::new("You are a helpful assistant.")
+//!     .with_summarizer(ChatClient::from_env("gpt-4.1-nano").unwrap());
+//!
+//! conversation.push(ChatMessage::user("Who was the first US president?"));
+//! let answer: String = client.chat_with_conversation(&mut conversation).await.unwrap();
+//! conversation.push(ChatMessage::assistant(answer));
+//! # })
+//! ```
+//!
+//! For typed multi-turn conversations, a [`Session`] (created with [`ChatClient::conversation`]) does the bookkeeping for you:
+//!
+//! ```rust,no_run
+//! # use tysm::chat_completions::ChatClient;
+//! # tokio_test::block_on(async {
+//! #[derive(serde::Deserialize, schemars::JsonSchema)]
+//! struct Name {
+//!     first: String,
+//!     last: String,
+//! }
+//!
+//! let client = ChatClient::from_env("gpt-4o").unwrap();
+//! let mut session = client.conversation("You are a helpful assistant.");
+//! let first: Name = session.ask("Who was the first US president?").await.unwrap();
+//! let second: Name = session.ask("And the one after that?").await.unwrap();
+//!
+//! // persist the session, and resume it later
+//! let json = serde_json::to_string(&session).unwrap();
+//! let session = client.resume(serde_json::from_str(&json).unwrap());
+//! # })
+//! ```
+
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::chat_completions::{
+    ChatClient, ChatError, ChatMessage, ChatMessageContent, ResponseFormat, Role,
+};
+use crate::usage::ASSUMED_MAX_COMPLETION_TOKENS;
+
+const SUMMARIZER_PROMPT: &str = "You will be given the beginning of a conversation between a user and an assistant \
+(possibly including a summary of an even earlier part of it). Summarize it concisely, keeping every fact, decision, \
+name and number that later messages might refer to. Reply with the summary only.";
+
+/// A conversation: a system prompt, a summary of any turns that no longer fit in the context window, and the rest of the history.
+///
+/// Conversations can be serialized, so that they can be persisted and resumed. The summarizer is not serialized.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Conversation {
+    /// The system prompt. It is always sent, and never trimmed.
+    pub system_prompt: String,
+    /// A summary of the turns that were dropped to fit the context window, if there is a summarizer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// The messages of the conversation, oldest first.
+    pub messages: Vec<ChatMessage>,
+    /// The client used to summarize dropped turns. Without one, dropped turns are forgotten.
+    #[serde(skip)]
+    pub summarizer: Option<Arc<ChatClient>>,
+}
+
+impl std::fmt::Debug for Conversation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Conversation")
+            .field("system_prompt", &self.system_prompt)
+            .field("summary", &self.summary)
+            .field("messages", &self.messages)
+            .field(
+                "summarizer",
+                &self.summarizer.as_ref().map(|client| &client.model),
+            )
+            .finish()
+    }
+}
+
+impl Conversation {
+    /// Create a new conversation with a system prompt.
+    pub fn new(system_prompt: impl Into<String>) -> Self {
+        Self {
+            system_prompt: system_prompt.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Summarize turns that no longer fit in the context window with `client`, instead of forgetting them.
+    pub fn with_summarizer(mut self, client: ChatClient) -> Self {
+        self.summarizer = Some(Arc::new(client));
+        self
+    }
+
+    /// Add a message to the end of the conversation.
+    pub fn push(&mut self, message: ChatMessage) {
+        self.messages.push(message);
+    }
+
+    /// The messages to send to the model: the system prompt, the summary (if any) and the history.
+    pub fn to_messages(&self) -> Vec<ChatMessage> {
+        let mut messages = Vec::with_capacity(self.messages.len() + 2);
+        if !self.system_prompt.is_empty() {
+            messages.push(ChatMessage::system(self.system_prompt.clone()));
+        }
+        if let Some(summary) = &self.summary {
+            messages.push(ChatMessage::system(format!(
+                "Summary of the earlier part of the conversation:\n{summary}"
+            )));
+        }
+        messages.extend(self.messages.iter().cloned());
+        messages
+    }
+
+    /// The number of prompt tokens the conversation would use with `model` (see [`crate::tokens::count`]).
+    pub fn tokens(&self, model: &str) -> u32 {
+        crate::tokens::count(model, &self.to_messages())
+    }
+
+    /// Drop (or summarize) the oldest turns until the conversation leaves at least `reserved_tokens`
+    /// of `model`'s context window free for the response.
+    ///
+    /// The system prompt and the newest message are always kept, so the conversation may still not fit if they are too long.
+    /// Models whose context window isn't known (see [`crate::models::info`]) are left alone.
+    pub async fn fit(&mut self, model: &str, reserved_tokens: u32) -> Result<(), ChatError> {
+        let Some(info) = crate::models::info(model) else {
+            return Ok(());
+        };
+        let limit = info.context_window.saturating_sub(reserved_tokens);
+
+        let mut tokens = self.tokens(model);
+        while tokens > limit && self.messages.len() > 1 {
+            let mut dropped = Vec::new();
+            while tokens > limit && self.messages.len() > 1 {
+                let message = self.messages.remove(0);
+                tokens -= crate::tokens::count_message(model, &message);
+                dropped.push(message);
+                // keep the history starting with a user turn
+                while self.messages.len() > 1 && self.messages[0].role == Role::Assistant {
+                    let message = self.messages.remove(0);
+                    tokens -= crate::tokens::count_message(model, &message);
+                    dropped.push(message);
+                }
+            }
+
+            if let Some(summarizer) = self.summarizer.clone() {
+                self.summary = Some(self.summarize(&summarizer, &dropped).await?);
+                tokens = self.tokens(model);
+            }
+        }
+        Ok(())
+    }
+
+    async fn summarize(
+        &self,
+        summarizer: &ChatClient,
+        dropped: &[ChatMessage],
+    ) -> Result<String, ChatError> {
+        let mut transcript = String::new();
+        if let Some(summary) = &self.summary {
+            transcript.push_str(&format!("Summary of what came before:\n{summary}\n\n"));
+        }
+        for message in dropped {
+            let role = serde_json::to_value(message.role).unwrap();
+            transcript.push_str(role.as_str().unwrap_or_default());
+            if let Some(name) = &message.name {
+                transcript.push_str(&format!(" ({name})"));
+            }
+            transcript.push_str(": ");
+            for part in &message.content {
+                match part {
+                    ChatMessageContent::Text { text } => transcript.push_str(text),
+                    ChatMessageContent::ImageUrl { .. } => transcript.push_str("[image]"),
+                    ChatMessageContent::InputAudio { .. } => transcript.push_str("[audio]"),
+                    ChatMessageContent::File { file } => transcript.push_str(&format!(
+                        "[file {}]",
+                        file.filename.as_deref().unwrap_or_default()
+                    )),
+                }
+            }
+            transcript.push('\n');
+        }
+
+        summarizer
+            .chat_with_messages_raw(
+                vec![
+                    ChatMessage::system(SUMMARIZER_PROMPT),
+                    ChatMessage::user(transcript),
+                ],
+                ResponseFormat::Text,
+            )
+            .await
+    }
+}
+
+impl ChatClient {
+    /// Send a conversation to the API, after fitting it into the model's context window (see [`Conversation::fit`]).
+    ///
+    /// Room is left for [`Self::max_tokens`] tokens of output (or 4096 if it isn't set). The reply is not added to the
+    /// conversation; push it yourself if you want the model to see it in later turns (or use a [`Session`], which does).
+    pub async fn chat_with_conversation<T: DeserializeOwned + JsonSchema>(
+        &self,
+        conversation: &mut Conversation,
+    ) -> Result<T, ChatError> {
+        self.fit_conversation(conversation).await?;
+        self.chat_with_messages(conversation.to_messages()).await
+    }
+
+    /// Start a typed multi-turn conversation with this client (see [`Session`]).
+    pub fn conversation(&self, system_prompt: impl Into<String>) -> Session<'_> {
+        self.resume(Conversation::new(system_prompt))
+    }
+
+    /// Continue a conversation, for example one that was persisted from an earlier [`Session`].
+    pub fn resume(&self, conversation: Conversation) -> Session<'_> {
+        Session {
+            client: self,
+            conversation,
+        }
+    }
+
+    async fn fit_conversation(&self, conversation: &mut Conversation) -> Result<(), ChatError> {
+        let reserved_tokens = self.max_tokens.unwrap_or(ASSUMED_MAX_COMPLETION_TOKENS);
+        conversation.fit(&self.model, reserved_tokens).await
+    }
+}
+
+/// A typed multi-turn conversation with a [`ChatClient`].
+///
+/// Each call to [`Self::ask`] adds the user's message and the model's reply to the conversation, so later questions
+/// can refer to earlier answers. The conversation is kept within the model's context window (see [`Conversation::fit`]).
+///
+/// A session serializes as its [`Conversation`], which can be resumed with [`ChatClient::resume`].
+pub struct Session<'a> {
+    /// The client the conversation is with.
+    pub client: &'a ChatClient,
+    /// The conversation so far.
+    pub conversation: Conversation,
+}
+
+impl Session<'_> {
+    /// Ask the model something, and get a typed reply.
+    ///
+    /// The question and the reply (as the JSON the model produced) are added to the conversation. If the request fails,
+    /// the conversation is left as it was.
+    pub async fn ask<T: DeserializeOwned + JsonSchema>(
+        &mut self,
+        message: impl Into<String>,
+    ) -> Result<T, ChatError> {
+        self.ask_with_message(ChatMessage::user(message)).await
+    }
+
+    /// Like [`Self::ask`], but with a message that isn't only text (such as one with images).
+    pub async fn ask_with_message<T: DeserializeOwned + JsonSchema>(
+        &mut self,
+        message: ChatMessage,
+    ) -> Result<T, ChatError> {
+        let mut conversation = self.conversation.clone();
+        conversation.push(message);
+        self.client.fit_conversation(&mut conversation).await?;
+
+        let (value, raw) = self
+            .client
+            .chat_with_messages_keeping_raw(conversation.to_messages())
+            .await?;
+        conversation.push(ChatMessage::assistant(raw));
+        self.conversation = conversation;
+        Ok(value)
+    }
+
+    /// End the session, returning the conversation.
+    pub fn into_conversation(self) -> Conversation {
+        self.conversation
+    }
+}
+
+impl Serialize for Session<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.conversation.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drops_oldest_turns() {
+        let mut conversation = Conversation::new("You are a helpful assistant.");
+        for i in 0..30 {
+            conversation.push(ChatMessage::user(format!("{i} ").repeat(500)));
+            conversation.push(ChatMessage::assistant("ok"));
+        }
+        conversation.push(ChatMessage::user("What was the last number?"));
+
+        // gpt-4 has an 8k context window
+        conversation.fit("gpt-4", 1000).await.unwrap();
+        assert!(conversation.tokens("gpt-4") <= 8_192 - 1000);
+        assert!(conversation.messages.len() < 61);
+        assert_eq!(conversation.messages[0].role, Role::User);
+        assert!(matches!(
+            &conversation.messages.last().unwrap().content[0],
+            ChatMessageContent::Text { text } if text == "What was the last number?"
+        ));
+        assert_eq!(
+            conversation.to_messages()[0].role,
+            Role::System,
+            "the system prompt is kept"
+        );
+
+        let json = serde_json::to_string(&conversation).unwrap();
+        let resumed: Conversation = serde_json::from_str(&json).unwrap();
+        assert_eq!(resumed.messages.len(), conversation.messages.len());
+    }
+
+    #[tokio::test]
+    async fn failed_questions_are_not_kept() {
+        // nothing is listening on port 1, so every request fails
+        let client = ChatClient::new("sk-1234567890", "gpt-4o").with_url("http://127.0.0.1:1/");
+        let mut session = client.resume(Conversation {
+            system_prompt: "You are a helpful assistant.".to_string(),
+            messages: vec![
+                ChatMessage::user("Hi!"),
+                ChatMessage::assistant("\"Hello!\""),
+            ],
+            ..Conversation::default()
+        });
+        assert!(session.ask::<String>("How are you?").await.is_err());
+        assert_eq!(session.conversation.messages.len(), 2);
+
+        let json = serde_json::to_value(&session).unwrap();
+        assert_eq!(json["system_prompt"], "You are a helpful assistant.");
+        assert_eq!(json["messages"][1]["role"], "assistant");
+    }
+}
@@ -1,6 +1,8 @@
 //! Embeddings are a way to represent text in a vector space.
 //! This module provides a client for interacting with the OpenAI Embeddings API.
 
+use std::sync::Arc;
+
 use itertools::Itertools;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -49,7 +51,10 @@ struct Usage {
 use thiserror::Error;
 
 use crate::{
-    utils::{api_key, OpenAiApiKeyError},
+    chat_completions::ChatUsage,
+    tokens,
+    usage::{BudgetExceeded, Endpoint, UsageLedger},
+    utils::{api_key, base_url, Authenticate, Authentication, OpenAiApiKeyError},
     OpenAiError,
 };
 
@@ -67,6 +72,10 @@ pub struct EmbeddingsClient {
     pub batch_size: usize,
     /// Some embedding models are trained using a technique that allows them to have their dimensionality lowered without the embedding losing its concept-representing properties. Of OpenAI's models, only text-embedding-3 and later models support this functionality.
     pub dimensions: Option<usize>,
+    /// How requests are authenticated. By default, this is [`Authentication::Bearer`].
+    pub authentication: Authentication,
+    /// The ledger in which token consumption is recorded. This can be shared with other clients (see [`Self::with_ledger`]).
+    pub ledger: Arc<UsageLedger>,
 }
 
 /// Errors that can occur when interacting with the ChatGPT API.
@@ -101,6 +110,10 @@ pub enum EmbeddingsError {
     /// The API did not return any choices.
     #[error("The wrong amount of embeddings was returned from API")]
     IncorrectNumberOfEmbeddings,
+
+    /// The request was not sent, because it could have pushed spending past the budget (see [`EmbeddingsClient::with_budget`]).
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetExceeded),
 }
 
 impl EmbeddingsClient {
@@ -120,6 +133,35 @@ impl EmbeddingsClient {
             model: model.into(),
             batch_size: 500,
             dimensions: None,
+            authentication: Authentication::Bearer,
+            ledger: Arc::new(UsageLedger::new()),
+        }
+    }
+
+    /// Create a new [`EmbeddingsClient`] for [Azure OpenAI](https://learn.microsoft.com/en-us/azure/ai-services/openai/).
+    ///
+    /// `endpoint` is your resource's endpoint (such as `https://my-resource.openai.azure.com/`),
+    /// and `deployment` is the name of your embedding model deployment.
+    ///
+    /// ```rust
+    /// use tysm::embeddings::EmbeddingsClient;
+    ///
+    /// let client = EmbeddingsClient::azure("https://my-resource.openai.azure.com/", "text-embedding-3-small", "2024-10-21", "YOUR AZURE API KEY");
+    /// ```
+    pub fn azure(
+        endpoint: impl Into<String>,
+        deployment: impl Into<String>,
+        api_version: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Self {
+        let deployment = deployment.into();
+        Self {
+            base_url: base_url(endpoint),
+            embeddings_path: format!("openai/deployments/{deployment}/embeddings"),
+            authentication: Authentication::Azure {
+                api_version: api_version.into(),
+            },
+            ..Self::new(api_key, deployment)
         }
     }
 
@@ -133,15 +175,7 @@ impl EmbeddingsClient {
     ///
     /// Panics if the argument is not a valid URL.
     pub fn with_url(mut self, url: impl Into<String>) -> Self {
-        let url = url.into();
-        let url = if url.ends_with('/') {
-            url
-        } else {
-            format!("{}/", url)
-        };
-
-        let url = url::Url::parse(&url).unwrap();
-        self.base_url = url;
+        self.base_url = base_url(url);
         self
     }
 
@@ -165,6 +199,28 @@ impl EmbeddingsClient {
         }
     }
 
+    /// Record this client's usage in `ledger`, so that it can be shared with other clients.
+    pub fn with_ledger(self, ledger: Arc<UsageLedger>) -> Self {
+        Self { ledger, ..self }
+    }
+
+    /// Refuse to send requests once they could push spending past `dollars`.
+    ///
+    /// Like [`ChatClient::with_budget`](crate::chat_completions::ChatClient::with_budget), the budget is stored in the
+    /// client's ledger, and applies to every client that shares it.
+    pub fn with_budget(self, dollars: f64) -> Self {
+        self.ledger.set_budget(Some(dollars));
+        self
+    }
+
+    /// Attempts to compute the cost in dollars of the usage of this client.
+    ///
+    /// This is the cost of everything in the client's [ledger](Self::ledger), so if the ledger is shared with other clients,
+    /// their usage is included too. If the price of any of the models is unknown, this returns `None`.
+    pub fn cost(&self) -> Option<f64> {
+        self.ledger.cost()
+    }
+
     fn embeddings_url(&self) -> url::Url {
         self.base_url.join(&self.embeddings_path).unwrap()
     }
@@ -219,6 +275,19 @@ impl EmbeddingsClient {
         for chunk in &documents {
             let (data, documents) = chunk.into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
             let documents_len = documents.len();
+            let prompt_tokens = documents
+                .iter()
+                .map(|document| tokens::count_text(&self.model, document))
+                .sum();
+            self.ledger.check_budget(
+                &self.model,
+                Endpoint::Embeddings,
+                ChatUsage {
+                    prompt_tokens,
+                    total_tokens: prompt_tokens,
+                    ..ChatUsage::default()
+                },
+            )?;
             let request = EmbeddingsRequest {
                 model: self.model.clone(),
                 input: documents,
@@ -227,7 +296,7 @@ impl EmbeddingsClient {
 
             let response = client
                 .post(self.embeddings_url())
-                .header("Authorization", format!("Bearer {}", self.api_key))
+                .authenticate(&self.authentication, &self.api_key)
                 .header("Content-Type", "application/json")
                 .json(&request)
                 .send()
@@ -258,6 +327,16 @@ impl EmbeddingsClient {
                 }
             };
 
+            self.ledger.record(
+                &embeddings_response.model,
+                Endpoint::Embeddings,
+                ChatUsage {
+                    prompt_tokens: embeddings_response.usage.prompt_tokens,
+                    total_tokens: embeddings_response.usage.total_tokens,
+                    ..ChatUsage::default()
+                },
+            );
+
             if embeddings_response.data.len() != documents_len {
                 return Err(EmbeddingsError::IncorrectNumberOfEmbeddings);
             }
@@ -0,0 +1,262 @@
+//! Extracting JSON from free-form model output.
+//!
+//! Models that don't support Structured Outputs tend to wrap their JSON in prose or markdown code fences,
+//! and sometimes produce JSON with comments or trailing commas. [`extract_json`] finds the JSON in such
+//! output and deserializes it into the type you asked for.
+
+use serde::de::DeserializeOwned;
+
+/// Extract a value of type `T` from text that contains JSON somewhere inside of it.
+///
+/// The candidates returned by [`json_candidates`] are tried in order, and the first one that deserializes into `T` is returned.
+/// This means that if the text contains several JSON values, the one matching the target type is picked.
+///
+/// ```rust
+/// use tysm::extract::extract_json;
+///
+/// #[derive(serde::Deserialize, Debug, PartialEq)]
+/// struct Name {
+///     first: String,
+///     last: String,
+/// }
+///
+/// let text = r#"Here's the name you asked for:
+/// ```json
+/// {
+///     "first": "George",
+///     // the last name
+///     "last": "Washington",
+/// }
+/// ```"#;
+/// let name: Name = extract_json(text).unwrap();
+/// assert_eq!(name, Name { first: "George".into(), last: "Washington".into() });
+/// ```
+///
+/// If no candidate deserializes into `T`, the error from the first candidate is returned.
+pub fn extract_json<T: DeserializeOwned>(text: &str) -> Result<T, serde_json::Error> {
+    let mut first_error = None;
+    for candidate in json_candidates(text) {
+        match serde_json::from_str(&candidate) {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        // there's always at least one candidate (the whole text), but just in case
+        None => serde_json::from_str(text),
+    }
+}
+
+/// Find all the strings in `text` that might be JSON, in the order they should be tried.
+///
+/// The candidates are:
+/// 1. the whole text
+/// 2. the contents of markdown code fences
+/// 3. every balanced JSON object or array (outermost first)
+/// 4. each line of the text
+///
+/// Candidates with comments or trailing commas are also included in a cleaned-up form.
+///
+/// The candidates are produced lazily, so if the whole text is valid JSON, nothing else is searched for.
+pub fn json_candidates(text: &str) -> impl Iterator<Item = String> + '_ {
+    let whole = std::iter::once(text.trim());
+
+    // the contents of code fences
+    let fences = text.split("```").skip(1).step_by(2).map(|block| {
+        let block = block.strip_prefix("json").unwrap_or(block);
+        block.trim()
+    });
+
+    // balanced objects and arrays
+    let spans = std::iter::once_with(|| balanced_spans(text))
+        .flatten()
+        .map(|(start, end)| &text[start..end]);
+
+    // single lines
+    let lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+    let mut seen = std::collections::HashSet::new();
+    whole
+        .chain(fences)
+        .chain(spans)
+        .chain(lines)
+        .flat_map(|candidate| {
+            std::iter::once(candidate.to_string()).chain(std::iter::once_with(|| {
+                remove_trailing_commas(&remove_comments(candidate))
+            }))
+        })
+        .filter(move |candidate| !candidate.is_empty() && seen.insert(candidate.clone()))
+}
+
+/// Returns the byte ranges of every balanced JSON object or array in `text`, outermost first.
+///
+/// This is a single pass with a stack of the objects and arrays that are still open.
+/// Quotes are only treated as strings inside objects and arrays, since outside of them they're just text.
+fn balanced_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    // the start of each open object or array, and the bracket that closes it
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' if !stack.is_empty() => in_string = true,
+            '{' => stack.push((i, '}')),
+            '[' => stack.push((i, ']')),
+            '}' | ']' => match stack.pop() {
+                Some((start, closing)) if closing == c => spans.push((start, i + c.len_utf8())),
+                // a mismatched bracket means that none of the open objects and arrays are balanced
+                Some(_) => stack.clear(),
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    spans.sort_unstable_by_key(|&(start, _)| start);
+    spans
+}
+
+/// Remove `//` and `/* */` comments that are outside of strings.
+fn remove_comments(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            output.push(c);
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                output.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        output.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+            }
+            _ => output.push(c),
+        }
+    }
+
+    output
+}
+
+/// Remove commas that directly precede a closing `}` or `]` (outside of strings).
+fn remove_trailing_commas(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut output = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        output.push(c);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Name {
+        first: String,
+        last: String,
+    }
+
+    #[test]
+    fn picks_the_candidate_matching_the_type() {
+        let text = r#"The answer is [1, 2, 3]. As an object: {"first": "Ada", "last": "Lovelace"} (note: "}" is fine)"#;
+
+        let numbers: Vec<u32> = extract_json(text).unwrap();
+        assert_eq!(numbers, vec![1, 2, 3]);
+
+        let name: Name = extract_json(text).unwrap();
+        assert_eq!(name.first, "Ada");
+        assert_eq!(name.last, "Lovelace");
+    }
+
+    #[test]
+    fn tolerates_comments_and_trailing_commas() {
+        let text = r#"{
+            "first": "http://example.com", /* a URL, not a comment */
+            "last": "Hopper", // trailing comma below
+        }"#;
+        let name: Name = extract_json(text).unwrap();
+        assert_eq!(name.first, "http://example.com");
+        assert_eq!(name.last, "Hopper");
+    }
+
+    #[test]
+    fn finds_nested_spans_in_order() {
+        let candidates = json_candidates(r#"x {"a": [1, {"b": 2}]} ] [3} {"c": "]"}"#);
+        let candidates = candidates.collect::<Vec<_>>();
+        assert_eq!(
+            candidates[1..4],
+            [r#"{"a": [1, {"b": 2}]}"#, r#"[1, {"b": 2}]"#, r#"{"b": 2}"#]
+        );
+        assert_eq!(candidates[4], r#"{"c": "]"}"#);
+    }
+
+    #[test]
+    fn reports_an_error_when_nothing_matches() {
+        assert!(extract_json::<Name>("I don't know.").is_err());
+        assert!(extract_json::<Name>(r#"{"first": "Ada"}"#).is_err());
+    }
+}
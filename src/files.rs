@@ -5,11 +5,12 @@ use reqwest::{multipart, Client};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use thiserror::Error;
-use tokio::fs::File;
-use tokio_util::codec::{BytesCodec, FramedRead};
 
 use crate::{
-    utils::{api_key, remove_trailing_slash, OpenAiApiKeyError},
+    utils::{
+        api_key, base_url, file_part, remove_trailing_slash, Authenticate, Authentication,
+        OpenAiApiKeyError,
+    },
     OpenAiError,
 };
 
@@ -22,14 +23,22 @@ pub struct FilesClient {
     pub base_url: url::Url,
     /// The path to the Files API.
     pub files_path: String,
+    /// How requests are authenticated. By default, this is [`Authentication::Bearer`].
+    pub authentication: Authentication,
 }
 
 impl From<&crate::chat_completions::ChatClient> for FilesClient {
     fn from(client: &crate::chat_completions::ChatClient) -> Self {
+        let authentication = client.provider.authentication();
+        let base_url = match authentication {
+            Authentication::Bearer => client.base_url.clone(),
+            Authentication::Azure { .. } => client.base_url.join("openai/").unwrap(),
+        };
         Self {
             api_key: client.api_key.clone(),
-            base_url: client.base_url.clone(),
+            base_url,
             files_path: "files/".to_string(),
+            authentication,
         }
     }
 }
@@ -156,6 +165,31 @@ impl FilesClient {
             api_key: api_key.into(),
             base_url: url::Url::parse("https://api.openai.com/v1/").unwrap(),
             files_path: "files/".to_string(),
+            authentication: Authentication::Bearer,
+        }
+    }
+
+    /// Create a new [`FilesClient`] for [Azure OpenAI](https://learn.microsoft.com/en-us/azure/ai-services/openai/).
+    ///
+    /// `endpoint` is your resource's endpoint, such as `https://my-resource.openai.azure.com/`.
+    ///
+    /// ```rust
+    /// use tysm::files::FilesClient;
+    ///
+    /// let client = FilesClient::azure("https://my-resource.openai.azure.com/", "2024-10-21", "YOUR AZURE API KEY");
+    /// ```
+    pub fn azure(
+        endpoint: impl Into<String>,
+        api_version: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: base_url(endpoint).join("openai/").unwrap(),
+            files_path: "files/".to_string(),
+            authentication: Authentication::Azure {
+                api_version: api_version.into(),
+            },
         }
     }
 
@@ -197,10 +231,7 @@ impl FilesClient {
             .and_then(|name| name.to_str())
             .ok_or(FilesError::InvalidFilePath)?;
 
-        let file = File::open(file_path).await?;
-        let stream = FramedRead::new(file, BytesCodec::new());
-        let file_part = multipart::Part::stream(reqwest::Body::wrap_stream(stream))
-            .file_name(file_name.to_string());
+        let file_part = file_part(file_path, file_name.to_string()).await?;
 
         let form = multipart::Form::new()
             .text("purpose", format!("{:?}", purpose).to_lowercase())
@@ -210,7 +241,7 @@ impl FilesClient {
         let url = remove_trailing_slash(self.files_url());
         let response = client
             .post(url.clone())
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .multipart(form)
             .send()
             .await?;
@@ -258,7 +289,7 @@ impl FilesClient {
         let url = remove_trailing_slash(self.files_url());
         let response = client
             .post(url.clone())
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .multipart(form)
             .send()
             .await?;
@@ -295,7 +326,7 @@ impl FilesClient {
         let client = Client::new();
         let response = client
             .get(self.files_url())
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .send()
             .await?;
 
@@ -318,7 +349,7 @@ impl FilesClient {
         let client = Client::new();
         let response = client
             .get(self.files_url().join(file_id).unwrap())
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .send()
             .await?;
 
@@ -341,7 +372,7 @@ impl FilesClient {
         let client = Client::new();
         let response = client
             .delete(self.files_url().join(file_id).unwrap())
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .send()
             .await?;
 
@@ -368,7 +399,7 @@ impl FilesClient {
             .unwrap();
         let response = client
             .get(url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authenticate(&self.authentication, &self.api_key)
             .send()
             .await?;
 
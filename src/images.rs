@@ -0,0 +1,563 @@
+//! Generating and editing images.
+//! This module provides a client for interacting with the OpenAI Images API.
+//!
+//! ```rust,no_run
+//! # use tysm::images::{ImagesClient, ImageOptions};
+//! # tokio_test::block_on(async {
+//! let client = ImagesClient::from_env("gpt-image-1").unwrap();
+//! let images = client
+//!     .generate("A watercolor of a lighthouse at dawn", &ImageOptions::default())
+//!     .await
+//!     .unwrap();
+//! images.data[0].save("lighthouse.png").await.unwrap();
+//! # })
+//! ```
+
+use std::path::Path;
+use std::sync::Arc;
+
+use base64::Engine;
+use reqwest::{multipart, Client};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    chat_completions::{sniff_image_mime, ChatUsage, PromptTokenDetails},
+    model_prices::PriceTable,
+    tokens,
+    usage::{BudgetExceeded, Endpoint, UsageLedger},
+    utils::{api_key, base_url, Authenticate, Authentication, OpenAiApiKeyError},
+    OpenAiError,
+};
+
+/// The most output tokens an image can take (a high-quality 1536x1024 image with `gpt-image-1`).
+/// Requests are assumed to use this many per image when checking them against the budget.
+const ASSUMED_TOKENS_PER_IMAGE: u32 = 6240;
+
+/// A client for interacting with the OpenAI Images API.
+pub struct ImagesClient {
+    /// The API key to use for the Images API.
+    pub api_key: String,
+    /// The URL of the API. Customize this if you are using a custom API that is compatible with OpenAI's.
+    pub base_url: url::Url,
+    /// The subpath to the images endpoints. By default, this is `images/`.
+    pub images_path: String,
+    /// The model to use, such as `gpt-image-1` or `dall-e-3`.
+    pub model: String,
+    /// How requests are authenticated. By default, this is [`Authentication::Bearer`].
+    pub authentication: Authentication,
+    /// The ledger in which token consumption is recorded. This can be shared with other clients (see [`Self::with_ledger`]).
+    ///
+    /// Only `gpt-image-1` reports its usage in tokens. DALL·E models are priced per image, so their usage isn't recorded.
+    pub ledger: Arc<UsageLedger>,
+}
+
+/// Options for generating images. Options left as `None` use the API's defaults.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ImageOptions {
+    /// The number of images to generate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// The size of the images, such as `1024x1024`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    /// The quality of the images: `low`, `medium` or `high` for `gpt-image-1`, and `standard` or `hd` for `dall-e-3`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    /// Whether to return the images' bytes or URLs. `gpt-image-1` always returns bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ImageResponseFormat>,
+}
+
+impl ImageOptions {
+    /// Set the number of images to generate.
+    pub fn with_n(mut self, n: u32) -> Self {
+        self.n = Some(n);
+        self
+    }
+
+    /// Set the size of the images, such as `1024x1024`.
+    pub fn with_size(mut self, size: impl Into<String>) -> Self {
+        self.size = Some(size.into());
+        self
+    }
+
+    /// Set the quality of the images.
+    pub fn with_quality(mut self, quality: impl Into<String>) -> Self {
+        self.quality = Some(quality.into());
+        self
+    }
+
+    /// Set whether to return the images' bytes or URLs.
+    pub fn with_response_format(mut self, response_format: ImageResponseFormat) -> Self {
+        self.response_format = Some(response_format);
+        self
+    }
+
+    fn form_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = Vec::new();
+        if let Some(n) = self.n {
+            fields.push(("n", n.to_string()));
+        }
+        if let Some(size) = &self.size {
+            fields.push(("size", size.clone()));
+        }
+        if let Some(quality) = &self.quality {
+            fields.push(("quality", quality.clone()));
+        }
+        if let Some(response_format) = self.response_format {
+            fields.push(("response_format", response_format.to_string()));
+        }
+        fields
+    }
+}
+
+/// How the API returns images.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageResponseFormat {
+    /// Links to the images, which expire after an hour.
+    Url,
+    /// The images' bytes, base64-encoded.
+    B64Json,
+}
+
+impl std::fmt::Display for ImageResponseFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageResponseFormat::Url => write!(f, "url"),
+            ImageResponseFormat::B64Json => write!(f, "b64_json"),
+        }
+    }
+}
+
+/// An image to upload for editing, or for making variations.
+#[derive(Debug, Clone)]
+pub struct ImageFile {
+    /// The name of the file, such as `photo.png`.
+    pub filename: String,
+    /// The contents of the file.
+    pub bytes: Vec<u8>,
+}
+
+impl ImageFile {
+    /// An image from bytes.
+    pub fn new(filename: impl Into<String>, bytes: Vec<u8>) -> Self {
+        Self {
+            filename: filename.into(),
+            bytes,
+        }
+    }
+
+    /// Read an image file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self::new(filename, std::fs::read(path)?))
+    }
+
+    fn into_part(self) -> Result<multipart::Part, ImagesError> {
+        let mime = sniff_image_mime(&self.bytes).unwrap_or("application/octet-stream");
+        Ok(multipart::Part::bytes(self.bytes)
+            .file_name(self.filename)
+            .mime_str(mime)?)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GenerationRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    #[serde(flatten)]
+    options: &'a ImageOptions,
+}
+
+/// The images returned by the API.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Images {
+    /// When the images were created, as a Unix timestamp.
+    pub created: u64,
+    /// The images.
+    pub data: Vec<GeneratedImage>,
+    /// The tokens used to generate the images. Only reported by `gpt-image-1`.
+    #[serde(default)]
+    pub usage: Option<ImageUsage>,
+}
+
+/// An image returned by the API.
+#[derive(Debug, Deserialize, Clone)]
+pub struct GeneratedImage {
+    /// The image's bytes, base64-encoded (if [`ImageResponseFormat::B64Json`] was requested).
+    #[serde(default)]
+    pub b64_json: Option<String>,
+    /// A link to the image (if [`ImageResponseFormat::Url`] was requested).
+    #[serde(default)]
+    pub url: Option<String>,
+    /// The prompt that `dall-e-3` actually used, after rewriting yours.
+    #[serde(default)]
+    pub revised_prompt: Option<String>,
+}
+
+impl GeneratedImage {
+    /// The image's bytes. If the API returned a URL, the image is downloaded.
+    pub async fn bytes(&self) -> Result<Vec<u8>, ImagesError> {
+        if let Some(b64_json) = &self.b64_json {
+            return Ok(base64::engine::general_purpose::STANDARD.decode(b64_json)?);
+        }
+        let url = self.url.as_ref().ok_or(ImagesError::NoImageData)?;
+        let response = Client::new().get(url).send().await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Save the image to `path`. If the API returned a URL, the image is downloaded.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), ImagesError> {
+        let bytes = self.bytes().await?;
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+}
+
+/// The token consumption of `gpt-image-1`.
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+pub struct ImageUsage {
+    /// The number of input tokens (text and images).
+    pub input_tokens: u32,
+    /// Details about the input tokens.
+    #[serde(default)]
+    pub input_tokens_details: ImageInputTokensDetails,
+    /// The number of output (image) tokens.
+    pub output_tokens: u32,
+    /// The total number of tokens used.
+    pub total_tokens: u32,
+}
+
+/// Details about the input tokens of an image request.
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct ImageInputTokensDetails {
+    /// The number of text tokens in the prompt.
+    pub text_tokens: u32,
+    /// The number of tokens of the input images.
+    pub image_tokens: u32,
+}
+
+impl From<ImageUsage> for ChatUsage {
+    fn from(usage: ImageUsage) -> Self {
+        ChatUsage {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: usage.total_tokens,
+            prompt_token_details: Some(PromptTokenDetails {
+                image_tokens: usage.input_tokens_details.image_tokens,
+                ..PromptTokenDetails::default()
+            }),
+            completion_token_details: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+enum ImagesResponseOrError {
+    #[serde(rename = "error")]
+    Error(OpenAiError),
+    #[serde(untagged)]
+    Images(Images),
+}
+
+/// Errors that can occur when interacting with the Images API.
+#[derive(Error, Debug)]
+pub enum ImagesError {
+    /// An error occurred when sending the request to the API.
+    #[error("Request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    /// An error occurred when deserializing the response from the API.
+    #[error("API {url} returned an unknown response: {response}")]
+    ApiParseError {
+        /// The URL of the API that returned the error.
+        url: String,
+        /// The response from the API.
+        response: String,
+        /// The error that occurred when deserializing the response.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// The API returned an error.
+    #[error("API returned an error response")]
+    ApiError(#[source] OpenAiError),
+
+    /// The image's base64 data could not be decoded.
+    #[error("Invalid base64 in the image: {0}")]
+    Base64Error(#[from] base64::DecodeError),
+
+    /// The API returned an image with neither bytes nor a URL.
+    #[error("The image has neither base64 data nor a URL")]
+    NoImageData,
+
+    /// An error occurred when reading or writing a file.
+    #[error("File error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// The request was not sent, because it could have pushed spending past the budget (see [`ImagesClient::with_budget`]).
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetExceeded),
+}
+
+impl ImagesClient {
+    /// Create a new [`ImagesClient`].
+    /// If the API key is in the environment, you can use the [`Self::from_env`] method instead.
+    ///
+    /// ```rust
+    /// use tysm::images::ImagesClient;
+    ///
+    /// let client = ImagesClient::new("sk-1234567890", "gpt-image-1");
+    /// ```
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: "https://api.openai.com/v1/".parse().unwrap(),
+            images_path: "images/".into(),
+            model: model.into(),
+            authentication: Authentication::Bearer,
+            ledger: Arc::new(UsageLedger::new()),
+        }
+    }
+
+    /// Create a new [`ImagesClient`].
+    /// This will use the `OPENAI_API_KEY` environment variable to set the API key.
+    /// It will also look in the `.env` file for an `OPENAI_API_KEY` variable (using dotenv).
+    ///
+    /// ```rust
+    /// # use tysm::images::ImagesClient;
+    /// let client = ImagesClient::from_env("gpt-image-1").unwrap();
+    /// ```
+    pub fn from_env(model: impl Into<String>) -> Result<Self, OpenAiApiKeyError> {
+        Ok(Self::new(api_key()?, model))
+    }
+
+    /// Sets the base URL
+    ///
+    /// Panics if the argument is not a valid URL.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = base_url(url);
+        self
+    }
+
+    /// Record this client's usage in `ledger`, so that it can be shared with other clients.
+    pub fn with_ledger(self, ledger: Arc<UsageLedger>) -> Self {
+        Self { ledger, ..self }
+    }
+
+    /// Price this client's usage with `prices`. See [`ChatClient::with_prices`](crate::chat_completions::ChatClient::with_prices).
+    pub fn with_prices(self, prices: PriceTable) -> Self {
+        self.ledger.set_prices(prices);
+        self
+    }
+
+    /// Refuse to send requests once they could push spending past `dollars`.
+    ///
+    /// Like [`ChatClient::with_budget`](crate::chat_completions::ChatClient::with_budget), the budget is stored in the
+    /// client's ledger, and applies to every client that shares it.
+    pub fn with_budget(self, dollars: f64) -> Self {
+        self.ledger.set_budget(Some(dollars));
+        self
+    }
+
+    /// Attempts to compute the cost in dollars of the usage of this client.
+    ///
+    /// This is the cost of everything in the client's [ledger](Self::ledger), so if the ledger is shared with other clients,
+    /// their usage is included too. If the price of any of the models is unknown, this returns `None`.
+    pub fn cost(&self) -> Option<f64> {
+        self.ledger.cost()
+    }
+
+    fn images_url(&self, endpoint: &str) -> url::Url {
+        self.base_url
+            .join(&self.images_path)
+            .unwrap()
+            .join(endpoint)
+            .unwrap()
+    }
+
+    /// Generate images from a prompt.
+    pub async fn generate(
+        &self,
+        prompt: &str,
+        options: &ImageOptions,
+    ) -> Result<Images, ImagesError> {
+        self.check_budget(prompt, options)?;
+        let request = GenerationRequest {
+            model: &self.model,
+            prompt,
+            options,
+        };
+        let request = Client::new()
+            .post(self.images_url("generations"))
+            .authenticate(&self.authentication, &self.api_key)
+            .json(&request);
+        self.send("generations", request).await
+    }
+
+    /// Edit an image according to a prompt. If a `mask` is given, only its fully transparent areas are edited.
+    ///
+    /// ```rust,no_run
+    /// # use tysm::images::{ImagesClient, ImageFile, ImageOptions};
+    /// # tokio_test::block_on(async {
+    /// let client = ImagesClient::from_env("gpt-image-1").unwrap();
+    /// let images = client
+    ///     .edit(
+    ///         ImageFile::from_path("room.png").unwrap(),
+    ///         Some(ImageFile::from_path("mask.png").unwrap()),
+    ///         "Add a cat on the sofa",
+    ///         &ImageOptions::default(),
+    ///     )
+    ///     .await
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub async fn edit(
+        &self,
+        image: ImageFile,
+        mask: Option<ImageFile>,
+        prompt: &str,
+        options: &ImageOptions,
+    ) -> Result<Images, ImagesError> {
+        self.check_budget(prompt, options)?;
+        let mut form = multipart::Form::new()
+            .text("model", self.model.clone())
+            .text("prompt", prompt.to_string())
+            .part("image", image.into_part()?);
+        if let Some(mask) = mask {
+            form = form.part("mask", mask.into_part()?);
+        }
+        for (name, value) in options.form_fields() {
+            form = form.text(name, value);
+        }
+        let request = Client::new()
+            .post(self.images_url("edits"))
+            .authenticate(&self.authentication, &self.api_key)
+            .multipart(form);
+        self.send("edits", request).await
+    }
+
+    /// Make variations of an image. Only `dall-e-2` supports this.
+    pub async fn variation(
+        &self,
+        image: ImageFile,
+        options: &ImageOptions,
+    ) -> Result<Images, ImagesError> {
+        self.check_budget("", options)?;
+        let mut form = multipart::Form::new()
+            .text("model", self.model.clone())
+            .part("image", image.into_part()?);
+        for (name, value) in options.form_fields() {
+            form = form.text(name, value);
+        }
+        let request = Client::new()
+            .post(self.images_url("variations"))
+            .authenticate(&self.authentication, &self.api_key)
+            .multipart(form);
+        self.send("variations", request).await
+    }
+
+    fn check_budget(&self, prompt: &str, options: &ImageOptions) -> Result<(), BudgetExceeded> {
+        let prompt_tokens = tokens::count_text(&self.model, prompt);
+        let completion_tokens = options.n.unwrap_or(1) * ASSUMED_TOKENS_PER_IMAGE;
+        self.ledger.check_budget(
+            &self.model,
+            Endpoint::Images,
+            ChatUsage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+                ..ChatUsage::default()
+            },
+        )
+    }
+
+    async fn send(
+        &self,
+        endpoint: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Images, ImagesError> {
+        let response_text = request.send().await?.text().await?;
+        let response: ImagesResponseOrError =
+            serde_json::from_str(&response_text).map_err(|e| ImagesError::ApiParseError {
+                url: self.images_url(endpoint).to_string(),
+                response: response_text.clone(),
+                error: e,
+            })?;
+        let images = match response {
+            ImagesResponseOrError::Images(images) => images,
+            ImagesResponseOrError::Error(error) => return Err(ImagesError::ApiError(error)),
+        };
+        if let Some(usage) = images.usage {
+            self.ledger
+                .record(&self.model, Endpoint::Images, usage.into());
+        }
+        Ok(images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn parse_images() {
+        let response = r#"{
+            "created": 1713833628,
+            "data": [{"b64_json": "aGVsbG8="}],
+            "usage": {
+                "total_tokens": 100,
+                "input_tokens": 50,
+                "output_tokens": 50,
+                "input_tokens_details": {"text_tokens": 10, "image_tokens": 40}
+            }
+        }"#;
+        let ImagesResponseOrError::Images(images) = serde_json::from_str(response).unwrap() else {
+            panic!("expected images");
+        };
+        assert_eq!(images.data[0].bytes().await.unwrap(), b"hello");
+        let usage = ChatUsage::from(images.usage.unwrap());
+        assert_eq!(usage.prompt_token_details.unwrap().image_tokens, 40);
+
+        let options = ImageOptions::default()
+            .with_size("1024x1024")
+            .with_response_format(ImageResponseFormat::B64Json);
+        let request = serde_json::to_value(GenerationRequest {
+            model: "dall-e-3",
+            prompt: "a cat",
+            options: &options,
+        })
+        .unwrap();
+        assert_eq!(request["size"], "1024x1024");
+        assert_eq!(request["response_format"], "b64_json");
+        assert!(request.get("n").is_none());
+        assert_eq!(
+            options.form_fields(),
+            vec![
+                ("size", "1024x1024".to_string()),
+                ("response_format", "b64_json".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn variations_respect_the_budget() {
+        let client = ImagesClient::new("sk-1234567890", "dall-e-2")
+            .with_prices(
+                PriceTable::new()
+                    .with_price("dall-e-2", crate::model_prices::ModelPrice::new(0.0, 100.0)),
+            )
+            .with_budget(0.01);
+        let result = client
+            .variation(ImageFile::new("cat.png", vec![]), &ImageOptions::default())
+            .await;
+        assert!(matches!(result, Err(ImagesError::BudgetExceeded(_))));
+    }
+}
@@ -43,15 +43,27 @@
 
 #![deny(missing_docs)]
 
+pub mod audio;
 pub mod batch;
 pub mod chat_completions;
+pub mod classify;
+pub mod conversation;
 pub mod embeddings;
+pub mod extract;
 pub mod files;
-mod model_prices;
+pub mod images;
+pub mod logprobs;
+pub mod model_prices;
+pub mod models;
+pub mod moderation;
+pub mod providers;
+pub mod responses;
 mod schema;
+pub mod tokens;
+pub mod usage;
 mod utils;
 
-pub use utils::OpenAiApiKeyError;
+pub use utils::{AnthropicApiKeyError, Authentication, OpenAiApiKeyError};
 
 /// Emitted by the OpenAI API when an error occurs.
 #[derive(Debug, serde::Deserialize)]
@@ -0,0 +1,422 @@
+//! Log probabilities of the tokens in a response, and how confident the model was in each field of a JSON response.
+//!
+//! Request log probabilities with [`ChatClient::with_logprobs`](crate::chat_completions::ChatClient::with_logprobs),
+//! and they are returned in [`ChatCompletion::logprobs`](crate::chat_completions::ChatCompletion::logprobs):
+//!
+//! ```rust,no_run
+//! # tokio_test::block_on(async {
+//! use tysm::chat_completions::{ChatClient, ChatMessage};
+//!
+//! #[derive(serde::Deserialize, schemars::JsonSchema)]
+//! enum Sentiment {
+//!     Positive,
+//!     Negative,
+//! }
+//!
+//! #[derive(serde::Deserialize, schemars::JsonSchema)]
+//! struct Classification {
+//!     sentiment: Sentiment,
+//! }
+//!
+//! let client = ChatClient::from_env("gpt-4o").unwrap().with_logprobs(0);
+//! let completion = client
+//!     .chat_with_messages_detailed::<Classification>(vec![ChatMessage::user("I love it!")])
+//!     .await
+//!     .unwrap();
+//! let confidence = completion.field_confidence().unwrap();
+//! println!("{:.1}% sure", confidence["/sentiment"] * 100.0);
+//! # })
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+/// Log probability information for a response.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LogProbs {
+    /// The tokens of the response's content.
+    #[serde(default)]
+    pub content: Option<Vec<TokenLogProb>>,
+    /// The tokens of the response's refusal, if the model refused.
+    #[serde(default)]
+    pub refusal: Option<Vec<TokenLogProb>>,
+}
+
+/// A token, and how likely the model thought it was.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenLogProb {
+    /// The token.
+    pub token: String,
+    /// The natural logarithm of the token's probability.
+    pub logprob: f64,
+    /// The token's UTF-8 bytes. A character can be split across several tokens, in which case `token` isn't valid on its own.
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
+    /// The most likely tokens at this position, if they were requested.
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogProb>,
+}
+
+/// One of the most likely tokens at a position.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopLogProb {
+    /// The token.
+    pub token: String,
+    /// The natural logarithm of the token's probability.
+    pub logprob: f64,
+    /// The token's UTF-8 bytes.
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
+}
+
+impl TokenLogProb {
+    /// The probability of the token, between 0 and 1.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+
+    fn len(&self) -> usize {
+        self.bytes
+            .as_ref()
+            .map(|bytes| bytes.len())
+            .unwrap_or(self.token.len())
+    }
+}
+
+impl LogProbs {
+    /// The text of the response's content, put back together from its tokens.
+    pub fn text(&self) -> String {
+        let bytes = self
+            .content
+            .iter()
+            .flatten()
+            .flat_map(|token| match &token.bytes {
+                Some(bytes) => bytes.clone(),
+                None => token.token.as_bytes().to_vec(),
+            })
+            .collect::<Vec<_>>();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// How confident the model was in each value of a JSON response, between 0 and 1.
+    ///
+    /// The values are keyed by their [JSON pointer](https://datatracker.ietf.org/doc/html/rfc6901) (such as `/sentiment`
+    /// or `/items/0/name`). Only strings, numbers, booleans and nulls are included, not the objects and arrays that contain them.
+    ///
+    /// A value's confidence is the probability of the tokens it was generated from, so it is the probability that the model
+    /// would have produced exactly that value. For enums, this is a good measure of how sure the model was of its answer.
+    /// If the response doesn't contain JSON, the map is empty.
+    pub fn field_confidence(&self) -> BTreeMap<String, f64> {
+        let tokens = self.content.as_deref().unwrap_or_default();
+        let token_spans = self.token_spans();
+        self.value_spans(&self.text())
+            .into_iter()
+            .map(|(path, span)| {
+                let logprob = tokens
+                    .iter()
+                    .zip(&token_spans)
+                    .filter(|(_, token)| token.start < span.end && span.start < token.end)
+                    .map(|(token, _)| token.logprob)
+                    .sum::<f64>();
+                (path, logprob.exp())
+            })
+            .collect()
+    }
+
+    /// How likely each of `candidates` was to be the string at `pointer` (a [JSON pointer](https://datatracker.ietf.org/doc/html/rfc6901)),
+    /// between 0 and 1.
+    ///
+    /// The probability of the string the model produced is exact. For the other candidates, the most likely tokens
+    /// (see [`TokenLogProb::top_logprobs`]) are used to find the probability that the model would have started producing the
+    /// candidate instead, assuming it would then have finished it. Candidates that weren't among the most likely tokens get 0.
+    /// So the more alternatives were requested, the more accurate this is.
+    ///
+    /// Returns `None` if there is no string at `pointer`.
+    pub fn value_probabilities<'a>(
+        &self,
+        pointer: &str,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Option<HashMap<String, f64>> {
+        let tokens = self.content.as_deref().unwrap_or_default();
+        let token_spans = self.token_spans();
+        let text = self.text();
+        let (_, span) = self
+            .value_spans(&text)
+            .into_iter()
+            .find(|(path, _)| path == pointer)?;
+        if text.as_bytes()[span.start] != b'"' {
+            return None;
+        }
+        // the value without its opening quote, but with its closing quote
+        let value_start = span.start + 1;
+        let chosen = &text[value_start..span.end];
+        let first = token_spans
+            .iter()
+            .position(|token| token.end > value_start)?;
+        let last = token_spans.iter().position(|token| token.end >= span.end)?;
+        // the part of the first token before the value (such as `":"`)
+        // (tokens can split characters, so their boundaries aren't always valid places to slice the text)
+        let lead = text.get(token_spans[first].start..value_start)?;
+
+        let probabilities = candidates
+            .into_iter()
+            .map(|candidate| {
+                let quoted = serde_json::to_string(candidate).unwrap();
+                let target = &quoted[1..];
+                if target == chosen {
+                    let logprob = tokens[first..=last]
+                        .iter()
+                        .map(|token| token.logprob)
+                        .sum::<f64>();
+                    return (candidate.to_string(), logprob.exp());
+                }
+
+                let mut logprob = 0.0;
+                for index in first..=last {
+                    let start = token_spans[index].start.max(value_start);
+                    let Some(rest) = text
+                        .get(value_start..start)
+                        .and_then(|matched| target.strip_prefix(matched))
+                    else {
+                        break;
+                    };
+                    let produced = text.get(start..token_spans[index].end).unwrap_or_default();
+                    let alternatives = tokens[index]
+                        .top_logprobs
+                        .iter()
+                        .filter_map(|top| {
+                            let token = match &top.bytes {
+                                Some(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                                None => top.token.clone(),
+                            };
+                            let token = if index == first {
+                                token.strip_prefix(lead)?.to_string()
+                            } else {
+                                token
+                            };
+                            let continues = !token.is_empty()
+                                && token != produced
+                                && (rest.starts_with(&token) || token.starts_with(rest));
+                            continues.then_some(top.logprob)
+                        })
+                        .collect::<Vec<_>>();
+                    if !alternatives.is_empty() {
+                        let probability = alternatives
+                            .iter()
+                            .map(|alternative| (logprob + alternative).exp())
+                            .sum::<f64>();
+                        return (candidate.to_string(), probability);
+                    }
+                    logprob += tokens[index].logprob;
+                }
+                (candidate.to_string(), 0.0)
+            })
+            .collect();
+        Some(probabilities)
+    }
+
+    /// Where each token starts and ends in [`Self::text`], in bytes.
+    fn token_spans(&self) -> Vec<Range<usize>> {
+        let mut offset = 0;
+        self.content
+            .iter()
+            .flatten()
+            .map(|token| {
+                let span = offset..offset + token.len();
+                offset = span.end;
+                span
+            })
+            .collect()
+    }
+
+    /// Where each string, number, boolean and null in the JSON in `text` is, keyed by JSON pointer.
+    fn value_spans(&self, text: &str) -> Vec<(String, Range<usize>)> {
+        let mut fields = Vec::new();
+        if let Some(start) = text.find(['{', '[']) {
+            let mut scanner = Scanner {
+                text: text.as_bytes(),
+                pos: start,
+            };
+            // a truncated or malformed response still gives the spans of the values before the error
+            let _ = scanner.value(String::new(), &mut fields);
+        }
+        fields
+    }
+}
+
+/// Finds where each value of a JSON document is, by byte offset.
+struct Scanner<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.text.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.skip_whitespace();
+        (self.text.get(self.pos) == Some(&byte)).then(|| self.pos += 1)
+    }
+
+    fn value(&mut self, path: String, fields: &mut Vec<(String, Range<usize>)>) -> Option<()> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.text.get(self.pos)? {
+            b'{' => {
+                self.pos += 1;
+                if self.expect(b'}').is_some() {
+                    return Some(());
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    let key = serde_json::from_slice::<String>(&self.text[key]).ok()?;
+                    self.expect(b':')?;
+                    let key = key.replace('~', "~0").replace('/', "~1");
+                    self.value(format!("{path}/{key}"), fields)?;
+                    if self.expect(b',').is_none() {
+                        return self.expect(b'}');
+                    }
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+                if self.expect(b']').is_some() {
+                    return Some(());
+                }
+                for index in 0.. {
+                    self.value(format!("{path}/{index}"), fields)?;
+                    if self.expect(b',').is_none() {
+                        break;
+                    }
+                }
+                self.expect(b']')
+            }
+            b'"' => {
+                let span = self.string()?;
+                fields.push((path, span));
+                Some(())
+            }
+            _ => {
+                while self
+                    .text
+                    .get(self.pos)
+                    .is_some_and(|byte| byte.is_ascii_alphanumeric() || b"+-.".contains(byte))
+                {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return None;
+                }
+                fields.push((path, start..self.pos));
+                Some(())
+            }
+        }
+    }
+
+    /// The span of the string at the current position, including its quotes.
+    fn string(&mut self) -> Option<Range<usize>> {
+        let start = self.pos;
+        if self.text.get(self.pos) != Some(&b'"') {
+            return None;
+        }
+        self.pos += 1;
+        loop {
+            match self.text.get(self.pos)? {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return Some(start..self.pos);
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confidence_per_field() {
+        let token = |token: &str, logprob: f64| TokenLogProb {
+            token: token.to_string(),
+            logprob,
+            bytes: Some(token.as_bytes().to_vec()),
+            top_logprobs: vec![],
+        };
+        let logprobs = LogProbs {
+            content: Some(vec![
+                token("{\"", 0.0),
+                token("sentiment", 0.0),
+                token("\":\"", 0.0),
+                token("Positive", 0.8f64.ln()),
+                token("\",\"", 0.0),
+                token("scores", 0.0),
+                token("\":[", 0.0),
+                token("1", 0.5f64.ln()),
+                token(",", 0.0),
+                token("2", 0.0),
+                token("]}", 0.0),
+            ]),
+            refusal: None,
+        };
+        assert_eq!(
+            logprobs.text(),
+            r#"{"sentiment":"Positive","scores":[1,2]}"#
+        );
+
+        let confidence = logprobs.field_confidence();
+        assert_eq!(confidence.len(), 3);
+        assert!((confidence["/sentiment"] - 0.8).abs() < 1e-9);
+        assert!((confidence["/scores/0"] - 0.5).abs() < 1e-9);
+        assert_eq!(confidence["/scores/1"], 1.0);
+    }
+
+    #[test]
+    fn probabilities_of_alternatives() {
+        let top = |token: &str, logprob: f64| TopLogProb {
+            token: token.to_string(),
+            logprob,
+            bytes: None,
+        };
+        let token = |token: &str, logprob: f64, top_logprobs: Vec<TopLogProb>| TokenLogProb {
+            token: token.to_string(),
+            logprob,
+            bytes: None,
+            top_logprobs,
+        };
+        let logprobs = LogProbs {
+            content: Some(vec![
+                token("{\"", 0.0, vec![]),
+                token("label", 0.0, vec![]),
+                token("\":\"", 0.0, vec![]),
+                token(
+                    "Positive",
+                    0.7f64.ln(),
+                    vec![
+                        top("Positive", 0.7f64.ln()),
+                        top("Neg", 0.2f64.ln()),
+                        top("Negative", 0.05f64.ln()),
+                    ],
+                ),
+                token("\"}", 0.0, vec![]),
+            ]),
+            refusal: None,
+        };
+        let probabilities = logprobs
+            .value_probabilities("/label", ["Positive", "Negative", "Neutral"])
+            .unwrap();
+        assert!((probabilities["Positive"] - 0.7).abs() < 1e-9);
+        assert!((probabilities["Negative"] - 0.25).abs() < 1e-9);
+        assert_eq!(probabilities["Neutral"], 0.0);
+        assert_eq!(logprobs.value_probabilities("/missing", ["Positive"]), None);
+    }
+}
@@ -1,194 +1,549 @@
-//! This module contains the pricing of different models.
-
-/// The cost of using a model, in dollars per million tokens.
-#[derive(Debug, Clone)]
-pub(crate) struct ModelCost {
-    pub(crate) name: &'static str,
-    /// The cost of input tokens, in dollars per million tokens.
-    pub(crate) input: f64,
-    /// The cost of cached input tokens, in dollars per million tokens.
-    pub(crate) cached_input: Option<f64>,
-    /// The cost of output tokens, in dollars per million tokens.
-    pub(crate) output: f64,
+//! The prices of models, used to compute the cost of the tokens a client has used.
+//!
+//! `tysm` ships with the prices of common OpenAI and Anthropic chat and embedding models (see [`PriceTable::default`]).
+//! For other models, such as fine-tuned or self-hosted ones, you can register prices yourself or load them from a file:
+//!
+//! ```rust
+//! use tysm::chat_completions::ChatClient;
+//! use tysm::model_prices::{ModelPrice, PriceTable};
+//!
+//! let prices = PriceTable::default()
+//!     .with_price("ft:gpt-4o-2024-08-06", ModelPrice::new(3.75, 15.00).with_cached_input(1.875))
+//!     .with_price("llama3.2", ModelPrice::new(0.0, 0.0));
+//! let client = ChatClient::new("sk-1234567890", "gpt-4o").with_prices(prices);
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::chat_completions::ChatUsage;
+use crate::usage::Endpoint;
+
+/// The price of using a model, in dollars per million tokens.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    /// The price of input tokens, in dollars per million tokens.
+    pub input: f64,
+    /// The price of cached input tokens, in dollars per million tokens. If `None`, cached tokens are priced like other input tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+    /// The price of input tokens written to the prompt cache, in dollars per million tokens. If `None`, they are priced like other input tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
+    /// The price of output tokens, in dollars per million tokens.
+    pub output: f64,
+    /// The price of audio input tokens, in dollars per million tokens. If `None`, they are priced like other input tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_input: Option<f64>,
+    /// The price of audio output tokens, in dollars per million tokens. If `None`, they are priced like other output tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_output: Option<f64>,
+    /// The price of image input tokens, in dollars per million tokens. If `None`, they are priced like other input tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_input: Option<f64>,
 }
 
-pub(crate) const CHAT_COMPLETIONS: &[ModelCost] = &[
-    // Anthropic
-    ModelCost {
-        name: "claude-3-7-sonnet",
-        input: 3.0,
-        cached_input: None,
-        output: 15.0,
-    },
-    ModelCost {
-        name: "claude-3-5-haiku",
-        input: 0.80,
-        cached_input: None,
-        output: 4.0,
-    },
-    ModelCost {
-        name: "claude-3-opus",
-        input: 15.0,
-        cached_input: None,
-        output: 75.0,
-    },
-    ModelCost {
-        name: "claude-opus-4",
-        input: 15.0,
-        cached_input: None,
-        output: 75.0,
-    },
-    ModelCost {
-        name: "claude-sonnet-4",
-        input: 3.0,
-        cached_input: None,
-        output: 15.0,
-    },
-    ModelCost {
-        name: "claude-haiku-4",
-        input: 0.80,
-        cached_input: None,
-        output: 4.0,
-    },
-    // OpenAI
-    // Copied from https://platform.openai.com/docs/pricing on 2025-04-17
-    ModelCost {
-        name: "gpt-4.1",
-        input: 2.00,
-        cached_input: Some(0.50),
-        output: 8.00,
-    },
-    ModelCost {
-        name: "gpt-4.1-mini",
-        input: 0.40,
-        cached_input: Some(0.10),
-        output: 1.60,
-    },
-    ModelCost {
-        name: "gpt-4.1-nano",
-        input: 0.10,
-        cached_input: Some(0.025),
-        output: 0.40,
-    },
-    ModelCost {
-        name: "gpt-4.5-preview",
-        input: 75.00,
-        cached_input: Some(37.50),
-        output: 150.00,
-    },
-    ModelCost {
-        name: "gpt-4o",
-        input: 2.50,
-        cached_input: Some(1.25),
-        output: 10.00,
-    },
-    ModelCost {
-        name: "gpt-4o-audio-preview",
-        input: 2.50,
-        cached_input: None,
-        output: 10.00,
-    },
-    ModelCost {
-        name: "gpt-4o-realtime-preview",
-        input: 5.00,
-        cached_input: Some(2.50),
-        output: 20.00,
-    },
-    ModelCost {
-        name: "gpt-4o-mini",
-        input: 0.15,
-        cached_input: Some(0.075),
-        output: 0.60,
-    },
-    ModelCost {
-        name: "gpt-4o-mini-audio-preview",
-        input: 0.15,
-        cached_input: None,
-        output: 0.60,
-    },
-    ModelCost {
-        name: "gpt-4o-mini-realtime-preview",
-        input: 0.60,
-        cached_input: Some(0.30),
-        output: 2.40,
-    },
-    ModelCost {
-        name: "o1",
-        input: 15.00,
-        cached_input: Some(7.50),
-        output: 60.00,
-    },
-    ModelCost {
-        name: "o1-pro",
-        input: 150.00,
-        cached_input: None,
-        output: 600.00,
-    },
-    ModelCost {
-        name: "o3",
-        input: 10.00,
-        cached_input: Some(2.50),
-        output: 40.00,
-    },
-    ModelCost {
-        name: "o4-mini",
-        input: 1.10,
-        cached_input: Some(0.275),
-        output: 4.40,
-    },
-    ModelCost {
-        name: "o3-mini",
-        input: 1.10,
-        cached_input: Some(0.55),
-        output: 4.40,
-    },
-    ModelCost {
-        name: "o1-mini",
-        input: 1.10,
-        cached_input: Some(0.55),
-        output: 4.40,
-    },
-    ModelCost {
-        name: "gpt-4o-mini-search-preview",
-        input: 0.15,
-        cached_input: None,
-        output: 0.60,
-    },
-    ModelCost {
-        name: "gpt-4o-search-preview",
-        input: 2.50,
-        cached_input: None,
-        output: 10.00,
-    },
-    ModelCost {
-        name: "computer-use-preview",
-        input: 3.00,
-        cached_input: None,
-        output: 12.00,
-    },
-];
+/// The base for entries in [`DEFAULT_PRICES`] that have no special prices for audio or images.
+const TEXT_ONLY: ModelPrice = ModelPrice {
+    input: 0.0,
+    cached_input: None,
+    cache_write: None,
+    output: 0.0,
+    audio_input: None,
+    audio_output: None,
+    image_input: None,
+};
 
-pub(crate) fn cost(model: &str, usage: crate::chat_completions::ChatUsage) -> Option<f64> {
-    let model_cost = CHAT_COMPLETIONS
-        .iter()
-        .find(|mc| model.starts_with(mc.name))?;
-    let (cached_prompt_tokens, uncached_prompt_tokens) =
-        if let Some(details) = usage.prompt_token_details {
-            (
-                details.cached_tokens,
-                usage.prompt_tokens - details.cached_tokens,
-            )
-        } else {
-            (0, usage.prompt_tokens)
-        };
+impl ModelPrice {
+    /// Create a new [`ModelPrice`] from the prices of input and output tokens, in dollars per million tokens.
+    pub fn new(input: f64, output: f64) -> Self {
+        Self {
+            input,
+            output,
+            ..TEXT_ONLY
+        }
+    }
 
-    let usage_cost = model_cost.input * uncached_prompt_tokens as f64 / 1_000_000.0
-        + model_cost.cached_input.unwrap_or(model_cost.input) * cached_prompt_tokens as f64
+    /// Set the price of cached input tokens, in dollars per million tokens.
+    pub fn with_cached_input(self, cached_input: f64) -> Self {
+        Self {
+            cached_input: Some(cached_input),
+            ..self
+        }
+    }
+
+    /// Set the price of input tokens written to the prompt cache, in dollars per million tokens.
+    pub fn with_cache_write(self, cache_write: f64) -> Self {
+        Self {
+            cache_write: Some(cache_write),
+            ..self
+        }
+    }
+
+    /// Set the prices of audio input and output tokens, in dollars per million tokens.
+    pub fn with_audio(self, audio_input: f64, audio_output: f64) -> Self {
+        Self {
+            audio_input: Some(audio_input),
+            audio_output: Some(audio_output),
+            ..self
+        }
+    }
+
+    /// Set the price of image input tokens, in dollars per million tokens.
+    pub fn with_image_input(self, image_input: f64) -> Self {
+        Self {
+            image_input: Some(image_input),
+            ..self
+        }
+    }
+
+    /// The cost in dollars of `usage`.
+    ///
+    /// Cached, cache write, audio and image tokens are priced separately when the usage breaks them down. Reasoning tokens
+    /// are billed as output tokens (and are already included in the completion tokens).
+    pub fn cost(&self, usage: ChatUsage) -> f64 {
+        let prompt = usage.prompt_token_details.unwrap_or_default();
+        let completion = usage.completion_token_details.unwrap_or_default();
+        let text_prompt_tokens = usage.prompt_tokens.saturating_sub(
+            prompt.cached_tokens
+                + prompt.cache_write_tokens
+                + prompt.audio_tokens
+                + prompt.image_tokens,
+        );
+        let text_completion_tokens = usage
+            .completion_tokens
+            .saturating_sub(completion.audio_tokens);
+
+        (self.input * text_prompt_tokens as f64
+            + self.cached_input.unwrap_or(self.input) * prompt.cached_tokens as f64
+            + self.cache_write.unwrap_or(self.input) * prompt.cache_write_tokens as f64
+            + self.audio_input.unwrap_or(self.input) * prompt.audio_tokens as f64
+            + self.image_input.unwrap_or(self.input) * prompt.image_tokens as f64
+            + self.output * text_completion_tokens as f64
+            + self.audio_output.unwrap_or(self.output) * completion.audio_tokens as f64)
             / 1_000_000.0
-        + model_cost.output * usage.completion_tokens as f64 / 1_000_000.0;
-    Some(usage_cost)
+    }
 }
 
+/// Batch requests (to the chat and embeddings APIs) are billed at half the price of regular requests.
+pub const BATCH_DISCOUNT: f64 = 0.5;
+
+/// A table of model prices.
+///
+/// A model is priced by the entry with the longest name that the model's name starts with. An exact match is always the longest,
+/// so `gpt-4o-mini` is priced as `gpt-4o-mini` rather than `gpt-4o`, and dated snapshots such as `gpt-4o-2024-08-06`
+/// are priced as `gpt-4o` unless they are registered separately.
+///
+/// In files, the table is a map from model names to [`ModelPrice`]s:
+///
+/// ```toml
+/// ["ft:gpt-4o-2024-08-06"]
+/// input = 3.75
+/// cached_input = 1.875
+/// output = 15.0
+///
+/// [llama3]
+/// input = 0.0
+/// output = 0.0
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+/// Errors that can occur when loading a [`PriceTable`].
+#[derive(Error, Debug)]
+pub enum PriceTableError {
+    /// The file could not be read.
+    #[error("Could not read the price table: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// The JSON could not be parsed as a price table.
+    #[error("Could not parse the price table as JSON: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// The TOML could not be parsed as a price table.
+    #[error("Could not parse the price table as TOML: {0}")]
+    TomlError(#[from] toml::de::Error),
+}
+
+impl Default for PriceTable {
+    /// The prices that ship with `tysm`. These may be out of date. If you notice that they are,
+    /// [please leave an issue](https://github.com/not-pizza/tysm)!
+    fn default() -> Self {
+        Self {
+            prices: DEFAULT_PRICES
+                .iter()
+                .map(|(name, price)| (name.to_string(), *price))
+                .collect(),
+        }
+    }
+}
+
+impl PriceTable {
+    /// Create an empty price table.
+    pub fn new() -> Self {
+        Self {
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Parse a price table from JSON.
+    pub fn from_json(json: &str) -> Result<Self, PriceTableError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Parse a price table from TOML.
+    pub fn from_toml(toml: &str) -> Result<Self, PriceTableError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Load a price table from a file. Files ending in `.toml` are parsed as TOML, and anything else as JSON.
+    ///
+    /// To add the prices in the file to the built-in ones, use `PriceTable::default().with_prices(PriceTable::from_file(path)?)`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PriceTableError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            Self::from_toml(&contents)
+        } else {
+            Self::from_json(&contents)
+        }
+    }
+
+    /// Register the price of a model, replacing any previous price for the same name.
+    pub fn register(&mut self, model: impl Into<String>, price: ModelPrice) {
+        self.prices.insert(model.into(), price);
+    }
+
+    /// Register the price of a model, replacing any previous price for the same name.
+    pub fn with_price(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.register(model, price);
+        self
+    }
+
+    /// Register all the prices in `other`, replacing the prices of models that are in both tables.
+    pub fn with_prices(mut self, other: PriceTable) -> Self {
+        self.prices.extend(other.prices);
+        self
+    }
+
+    /// The price of `model`.
+    pub fn get(&self, model: &str) -> Option<&ModelPrice> {
+        self.prices
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| price)
+    }
+
+    /// The cost in dollars of `usage` of `model`, if the price of the model is known.
+    pub fn cost(&self, model: &str, usage: ChatUsage) -> Option<f64> {
+        Some(self.get(model)?.cost(usage))
+    }
+
+    /// The cost of `usage` of `model` through a particular endpoint.
+    pub(crate) fn endpoint_cost(
+        &self,
+        model: &str,
+        endpoint: Endpoint,
+        usage: ChatUsage,
+    ) -> Option<f64> {
+        match endpoint {
+            Endpoint::Chat | Endpoint::Responses | Endpoint::Images | Endpoint::Embeddings => {
+                self.cost(model, usage)
+            }
+            Endpoint::Batch => self.cost(model, usage).map(|cost| cost * BATCH_DISCOUNT),
+        }
+    }
+}
+
+const DEFAULT_PRICES: &[(&str, ModelPrice)] = &[
+    // Anthropic (writing to the prompt cache costs 1.25 times the input price)
+    (
+        "claude-3-7-sonnet",
+        ModelPrice {
+            input: 3.0,
+            cached_input: None,
+            cache_write: Some(3.75),
+            output: 15.0,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "claude-3-5-haiku",
+        ModelPrice {
+            input: 0.80,
+            cached_input: None,
+            cache_write: Some(1.0),
+            output: 4.0,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "claude-3-opus",
+        ModelPrice {
+            input: 15.0,
+            cached_input: None,
+            cache_write: Some(18.75),
+            output: 75.0,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "claude-opus-4",
+        ModelPrice {
+            input: 15.0,
+            cached_input: None,
+            cache_write: Some(18.75),
+            output: 75.0,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "claude-sonnet-4",
+        ModelPrice {
+            input: 3.0,
+            cached_input: None,
+            cache_write: Some(3.75),
+            output: 15.0,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "claude-haiku-4",
+        ModelPrice {
+            input: 0.80,
+            cached_input: None,
+            cache_write: Some(1.0),
+            output: 4.0,
+            ..TEXT_ONLY
+        },
+    ),
+    // OpenAI
+    // Copied from https://platform.openai.com/docs/pricing on 2025-04-17
+    (
+        "gpt-4.1",
+        ModelPrice {
+            input: 2.00,
+            cached_input: Some(0.50),
+            output: 8.00,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4.1-mini",
+        ModelPrice {
+            input: 0.40,
+            cached_input: Some(0.10),
+            output: 1.60,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4.1-nano",
+        ModelPrice {
+            input: 0.10,
+            cached_input: Some(0.025),
+            output: 0.40,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4.5-preview",
+        ModelPrice {
+            input: 75.00,
+            cached_input: Some(37.50),
+            output: 150.00,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4o",
+        ModelPrice {
+            input: 2.50,
+            cached_input: Some(1.25),
+            output: 10.00,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4o-audio-preview",
+        ModelPrice {
+            input: 2.50,
+            cached_input: None,
+            output: 10.00,
+            audio_input: Some(40.00),
+            audio_output: Some(80.00),
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4o-realtime-preview",
+        ModelPrice {
+            input: 5.00,
+            cached_input: Some(2.50),
+            output: 20.00,
+            audio_input: Some(40.00),
+            audio_output: Some(80.00),
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4o-mini",
+        ModelPrice {
+            input: 0.15,
+            cached_input: Some(0.075),
+            output: 0.60,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4o-mini-audio-preview",
+        ModelPrice {
+            input: 0.15,
+            cached_input: None,
+            output: 0.60,
+            audio_input: Some(10.00),
+            audio_output: Some(20.00),
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4o-mini-realtime-preview",
+        ModelPrice {
+            input: 0.60,
+            cached_input: Some(0.30),
+            output: 2.40,
+            audio_input: Some(10.00),
+            audio_output: Some(20.00),
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "o1",
+        ModelPrice {
+            input: 15.00,
+            cached_input: Some(7.50),
+            output: 60.00,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "o1-pro",
+        ModelPrice {
+            input: 150.00,
+            cached_input: None,
+            output: 600.00,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "o3",
+        ModelPrice {
+            input: 10.00,
+            cached_input: Some(2.50),
+            output: 40.00,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "o4-mini",
+        ModelPrice {
+            input: 1.10,
+            cached_input: Some(0.275),
+            output: 4.40,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "o3-mini",
+        ModelPrice {
+            input: 1.10,
+            cached_input: Some(0.55),
+            output: 4.40,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "o1-mini",
+        ModelPrice {
+            input: 1.10,
+            cached_input: Some(0.55),
+            output: 4.40,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4o-mini-search-preview",
+        ModelPrice {
+            input: 0.15,
+            cached_input: None,
+            output: 0.60,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-4o-search-preview",
+        ModelPrice {
+            input: 2.50,
+            cached_input: None,
+            output: 10.00,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "computer-use-preview",
+        ModelPrice {
+            input: 3.00,
+            cached_input: None,
+            output: 12.00,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "gpt-image-1",
+        ModelPrice {
+            input: 5.00,
+            cached_input: Some(1.25),
+            output: 40.00,
+            image_input: Some(10.00),
+            ..TEXT_ONLY
+        },
+    ),
+    // Embeddings
+    (
+        "text-embedding-3-small",
+        ModelPrice {
+            input: 0.02,
+            output: 0.0,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "text-embedding-3-large",
+        ModelPrice {
+            input: 0.13,
+            output: 0.0,
+            ..TEXT_ONLY
+        },
+    ),
+    (
+        "text-embedding-ada-002",
+        ModelPrice {
+            input: 0.10,
+            output: 0.0,
+            ..TEXT_ONLY
+        },
+    ),
+];
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,14 +555,96 @@ mod tests {
             completion_tokens: 1000000,
             prompt_token_details: Some(crate::chat_completions::PromptTokenDetails {
                 cached_tokens: 1000000,
+                ..Default::default()
             }),
             completion_token_details: None,
             total_tokens: 2000000,
         };
-        let cost = cost("gpt-4o", usage);
+        let cost = PriceTable::default().cost("gpt-4o", usage);
         // input: 2.50,
         // cached_input: Some(1.25),
         // output: 10.00,
         assert_eq!(cost, Some(2.50 + 1.25 + 10.00));
     }
+
+    #[test]
+    fn cache_writes_cost_more() {
+        let usage = crate::chat_completions::ChatUsage {
+            prompt_tokens: 2_000_000,
+            completion_tokens: 0,
+            prompt_token_details: Some(crate::chat_completions::PromptTokenDetails {
+                cache_write_tokens: 1_000_000,
+                ..Default::default()
+            }),
+            completion_token_details: None,
+            total_tokens: 2_000_000,
+        };
+        // input: 3.00, cache_write: Some(3.75)
+        assert_eq!(
+            PriceTable::default().cost("claude-sonnet-4-20250514", usage),
+            Some(3.00 + 3.75)
+        );
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let prices = PriceTable::default();
+        assert_eq!(prices.get("gpt-4o-mini").unwrap().input, 0.15);
+        assert_eq!(prices.get("gpt-4o-mini-2024-07-18").unwrap().input, 0.15);
+        assert_eq!(prices.get("gpt-4o-2024-08-06").unwrap().input, 2.50);
+        assert_eq!(prices.get("o1-pro").unwrap().input, 150.00);
+        assert_eq!(prices.get("ft:gpt-4o-2024-08-06:my-org::abc123"), None);
+
+        let prices = prices.with_prices(
+            PriceTable::from_toml(
+                r#"
+                ["ft:gpt-4o-2024-08-06"]
+                input = 3.75
+                cached_input = 1.875
+                output = 15.0
+                "#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            prices.get("ft:gpt-4o-2024-08-06:my-org::abc123"),
+            Some(&ModelPrice::new(3.75, 15.0).with_cached_input(1.875))
+        );
+
+        let json = serde_json::to_string(&prices).unwrap();
+        assert_eq!(PriceTable::from_json(&json).unwrap(), prices);
+    }
+
+    #[test]
+    fn audio_and_embeddings() {
+        let prices = PriceTable::default();
+        let usage = crate::chat_completions::ChatUsage {
+            prompt_tokens: 2_000_000,
+            completion_tokens: 2_000_000,
+            total_tokens: 4_000_000,
+            prompt_token_details: Some(crate::chat_completions::PromptTokenDetails {
+                audio_tokens: 1_000_000,
+                ..Default::default()
+            }),
+            completion_token_details: Some(crate::chat_completions::CompletionTokenDetails {
+                audio_tokens: 1_000_000,
+                ..Default::default()
+            }),
+        };
+        // text input: 2.50, audio input: 40.00, text output: 10.00, audio output: 80.00
+        assert_eq!(
+            prices.cost("gpt-4o-audio-preview-2024-12-17", usage),
+            Some(2.50 + 40.00 + 10.00 + 80.00)
+        );
+
+        let usage = crate::chat_completions::ChatUsage {
+            prompt_tokens: 1_000_000,
+            total_tokens: 1_000_000,
+            ..Default::default()
+        };
+        assert_eq!(
+            prices.endpoint_cost("text-embedding-3-large", Endpoint::Embeddings, usage),
+            Some(0.13)
+        );
+    }
 }
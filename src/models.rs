@@ -0,0 +1,407 @@
+//! What known models are capable of: their context windows, output limits, and which features they support.
+//!
+//! The typed methods use this to fail fast with a clear error (such as [`Unsupported`]) instead of sending a request
+//! the API would reject. Models that aren't listed here are assumed to support everything. Their prices are kept
+//! separately, in [`model_prices`](crate::model_prices).
+//!
+//! ```rust
+//! use tysm::models::{self, Capability};
+//!
+//! let info = models::info("gpt-4o-mini-2024-07-18").unwrap();
+//! assert_eq!(info.context_window, 128_000);
+//! assert!(info.supports(Capability::Vision));
+//! ```
+
+use thiserror::Error;
+
+use crate::chat_completions::{ChatMessage, Role};
+
+/// A feature that a model may or may not support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// `json_schema` response formats ([Structured Outputs](https://platform.openai.com/docs/guides/structured-outputs)).
+    StructuredOutputs,
+    /// Images in the prompt.
+    Vision,
+    /// Tool (function) calls.
+    Tools,
+    /// The `reasoning_effort` parameter.
+    ReasoningEffort,
+    /// Log probabilities of the response's tokens (the `logprobs` and `top_logprobs` parameters).
+    Logprobs,
+    /// OpenAI's batch API.
+    Batch,
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::StructuredOutputs => write!(f, "structured outputs"),
+            Capability::Vision => write!(f, "image inputs"),
+            Capability::Tools => write!(f, "tool calls"),
+            Capability::ReasoningEffort => write!(f, "reasoning effort"),
+            Capability::Logprobs => write!(f, "log probabilities"),
+            Capability::Batch => write!(f, "the batch API"),
+        }
+    }
+}
+
+/// What a model is capable of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelInfo {
+    /// The maximum number of tokens in the prompt and the completion combined.
+    pub context_window: u32,
+    /// The maximum number of tokens the model can generate (including reasoning tokens).
+    pub max_output_tokens: u32,
+    /// Whether the model supports `json_schema` response formats.
+    pub structured_outputs: bool,
+    /// Whether the model accepts images.
+    pub vision: bool,
+    /// Whether the model can call tools.
+    pub tools: bool,
+    /// Whether the model reasons before answering. Reasoning models take `max_completion_tokens` instead of `max_tokens`,
+    /// and their reasoning tokens count towards it.
+    pub reasoning: bool,
+    /// Whether the model accepts the `reasoning_effort` parameter.
+    pub reasoning_effort: bool,
+    /// Whether the model expects developer messages instead of system messages.
+    pub developer_role: bool,
+    /// Whether the model can return the log probabilities of its tokens.
+    pub logprobs: bool,
+    /// Whether the model can be used through OpenAI's batch API.
+    pub batch: bool,
+}
+
+impl ModelInfo {
+    /// Whether the model supports `capability`.
+    pub fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::StructuredOutputs => self.structured_outputs,
+            Capability::Vision => self.vision,
+            Capability::Tools => self.tools,
+            Capability::ReasoningEffort => self.reasoning_effort,
+            Capability::Logprobs => self.logprobs,
+            Capability::Batch => self.batch,
+        }
+    }
+}
+
+/// A request was not sent, because the model doesn't support a feature the request uses.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("The model `{model}` does not support {capability}")]
+pub struct Unsupported {
+    /// The model the request was for.
+    pub model: String,
+    /// The feature the model doesn't support.
+    pub capability: Capability,
+}
+
+/// Check that `model` supports `capability`. Models that aren't known are assumed to support everything.
+pub fn require(model: &str, capability: Capability) -> Result<(), Unsupported> {
+    match info(model) {
+        Some(info) if !info.supports(capability) => Err(Unsupported {
+            model: model.to_string(),
+            capability,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Look up what `model` is capable of.
+///
+/// Like prices, models are matched by the longest known name that the model's name starts with,
+/// so dated snapshots (such as `gpt-4o-2024-08-06`) are described by their base model unless they are listed separately.
+pub fn info(model: &str) -> Option<ModelInfo> {
+    MODELS
+        .iter()
+        .filter(|(name, _)| model.starts_with(name))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, info)| *info)
+}
+
+/// Convert system messages to developer messages if `model` expects them (see [`ModelInfo::developer_role`]).
+pub(crate) fn adapt_roles(model: &str, mut messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    if info(model).is_some_and(|info| info.developer_role) {
+        for message in &mut messages {
+            if message.role == Role::System {
+                message.role = Role::Developer;
+            }
+        }
+    }
+    messages
+}
+
+const GPT_4_1: ModelInfo = ModelInfo {
+    context_window: 1_047_576,
+    max_output_tokens: 32_768,
+    structured_outputs: true,
+    vision: true,
+    tools: true,
+    reasoning: false,
+    reasoning_effort: false,
+    developer_role: false,
+    logprobs: true,
+    batch: true,
+};
+
+const GPT_4O: ModelInfo = ModelInfo {
+    context_window: 128_000,
+    max_output_tokens: 16_384,
+    structured_outputs: true,
+    vision: true,
+    tools: true,
+    reasoning: false,
+    reasoning_effort: false,
+    developer_role: false,
+    logprobs: true,
+    batch: true,
+};
+
+const REASONING: ModelInfo = ModelInfo {
+    context_window: 200_000,
+    max_output_tokens: 100_000,
+    structured_outputs: true,
+    vision: true,
+    tools: true,
+    reasoning: true,
+    reasoning_effort: true,
+    developer_role: true,
+    logprobs: false,
+    batch: true,
+};
+
+const CLAUDE: ModelInfo = ModelInfo {
+    context_window: 200_000,
+    max_output_tokens: 64_000,
+    structured_outputs: true,
+    vision: true,
+    tools: true,
+    reasoning: false,
+    reasoning_effort: false,
+    developer_role: false,
+    logprobs: false,
+    batch: false,
+};
+
+const MODELS: &[(&str, ModelInfo)] = &[
+    // Anthropic
+    // Structured outputs are supported through the `AnthropicProvider`, which sends the schema as a tool.
+    ("claude-3-7-sonnet", CLAUDE),
+    (
+        "claude-3-5-haiku",
+        ModelInfo {
+            max_output_tokens: 8_192,
+            ..CLAUDE
+        },
+    ),
+    (
+        "claude-3-opus",
+        ModelInfo {
+            max_output_tokens: 4_096,
+            ..CLAUDE
+        },
+    ),
+    (
+        "claude-opus-4",
+        ModelInfo {
+            max_output_tokens: 32_000,
+            ..CLAUDE
+        },
+    ),
+    ("claude-sonnet-4", CLAUDE),
+    // OpenAI
+    ("gpt-4.1", GPT_4_1),
+    ("gpt-4.1-mini", GPT_4_1),
+    ("gpt-4.1-nano", GPT_4_1),
+    ("gpt-4o", GPT_4O),
+    (
+        "gpt-4o-2024-05-13",
+        ModelInfo {
+            max_output_tokens: 4_096,
+            structured_outputs: false,
+            ..GPT_4O
+        },
+    ),
+    ("gpt-4o-mini", GPT_4O),
+    (
+        "gpt-4o-audio-preview",
+        ModelInfo {
+            structured_outputs: false,
+            vision: false,
+            batch: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "gpt-4o-mini-audio-preview",
+        ModelInfo {
+            structured_outputs: false,
+            vision: false,
+            batch: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "gpt-4o-search-preview",
+        ModelInfo {
+            vision: false,
+            tools: false,
+            logprobs: false,
+            batch: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "gpt-4o-mini-search-preview",
+        ModelInfo {
+            vision: false,
+            tools: false,
+            logprobs: false,
+            batch: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "chatgpt-4o-latest",
+        ModelInfo {
+            structured_outputs: false,
+            tools: false,
+            batch: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "gpt-4-turbo",
+        ModelInfo {
+            max_output_tokens: 4_096,
+            structured_outputs: false,
+            ..GPT_4O
+        },
+    ),
+    ("gpt-4.5", GPT_4O),
+    (
+        "gpt-4-turbo-preview",
+        ModelInfo {
+            max_output_tokens: 4_096,
+            structured_outputs: false,
+            vision: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "gpt-4-0125-preview",
+        ModelInfo {
+            max_output_tokens: 4_096,
+            structured_outputs: false,
+            vision: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "gpt-4-1106-preview",
+        ModelInfo {
+            max_output_tokens: 4_096,
+            structured_outputs: false,
+            vision: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "gpt-4",
+        ModelInfo {
+            context_window: 8_192,
+            max_output_tokens: 8_192,
+            structured_outputs: false,
+            vision: false,
+            ..GPT_4O
+        },
+    ),
+    (
+        "gpt-3.5-turbo",
+        ModelInfo {
+            context_window: 16_385,
+            max_output_tokens: 4_096,
+            structured_outputs: false,
+            vision: false,
+            ..GPT_4O
+        },
+    ),
+    ("o1", REASONING),
+    (
+        "o1-pro",
+        ModelInfo {
+            batch: false,
+            ..REASONING
+        },
+    ),
+    (
+        "o1-mini",
+        ModelInfo {
+            context_window: 128_000,
+            max_output_tokens: 65_536,
+            structured_outputs: false,
+            vision: false,
+            tools: false,
+            reasoning_effort: false,
+            developer_role: false,
+            ..REASONING
+        },
+    ),
+    (
+        "o1-preview",
+        ModelInfo {
+            context_window: 128_000,
+            max_output_tokens: 32_768,
+            structured_outputs: false,
+            vision: false,
+            tools: false,
+            reasoning_effort: false,
+            developer_role: false,
+            ..REASONING
+        },
+    ),
+    ("o3", REASONING),
+    (
+        "o3-mini",
+        ModelInfo {
+            vision: false,
+            ..REASONING
+        },
+    ),
+    ("o4-mini", REASONING),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_prefix_wins() {
+        assert!(info("gpt-4o-2024-08-06").unwrap().structured_outputs);
+        assert!(!info("gpt-4o-2024-05-13").unwrap().structured_outputs);
+        assert_eq!(info("gpt-4-0613").unwrap().context_window, 8_192);
+        let gpt_4_5 = info("gpt-4.5-preview").unwrap();
+        assert_eq!(gpt_4_5.context_window, 128_000);
+        assert!(gpt_4_5.structured_outputs && gpt_4_5.vision);
+        assert_eq!(info("gpt-4-0125-preview").unwrap().context_window, 128_000);
+        assert_eq!(info("gpt-4-1106-preview").unwrap().context_window, 128_000);
+        assert_eq!(
+            info("gpt-4-turbo-2024-04-09").unwrap().context_window,
+            128_000
+        );
+        assert_eq!(
+            require("o3-mini", Capability::Vision),
+            Err(Unsupported {
+                model: "o3-mini".to_string(),
+                capability: Capability::Vision,
+            })
+        );
+        assert!(require("my-fine-tuned-llama", Capability::Vision).is_ok());
+        assert!(require("o4-mini", Capability::Logprobs).is_err());
+        assert!(require("gpt-4.1-mini", Capability::Logprobs).is_ok());
+
+        let messages = vec![ChatMessage::system("Be brief.")];
+        assert_eq!(adapt_roles("o3", messages.clone())[0].role, Role::Developer);
+        assert_eq!(adapt_roles("gpt-4o", messages)[0].role, Role::System);
+    }
+}
@@ -0,0 +1,516 @@
+//! Moderation checks whether text or images are harmful, such as harassment, hate, or violence.
+//! This module provides a client for interacting with the OpenAI Moderation API.
+//!
+//! ```rust,no_run
+//! # use tysm::moderation::ModerationClient;
+//! # tokio_test::block_on(async {
+//! let client = ModerationClient::from_env().unwrap();
+//! let moderations = client.moderate(&["I will find you.".to_string()]).await.unwrap();
+//! if moderations[0].flagged {
+//!     println!("flagged for {:?}", moderations[0].flagged_categories());
+//! }
+//! # })
+//! ```
+
+use reqwest::Client;
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+use crate::chat_completions::ImageUrl;
+use crate::utils::{api_key, base_url, Authenticate, Authentication, OpenAiApiKeyError};
+use crate::OpenAiError;
+
+/// The model used by default. It accepts both text and images.
+pub const DEFAULT_MODERATION_MODEL: &str = "omni-moderation-latest";
+
+#[derive(Debug, Serialize)]
+struct ModerationRequest<'a, I: Serialize> {
+    model: &'a str,
+    input: &'a [I],
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResponse {
+    results: Vec<Moderation>,
+}
+
+#[derive(Debug, Deserialize)]
+enum ModerationResponseOrError {
+    #[serde(rename = "error")]
+    Error(OpenAiError),
+    #[serde(untagged)]
+    Response(ModerationResponse),
+}
+
+/// A part of the input to moderate. Text and images can be moderated together with [`ModerationClient::moderate_inputs`].
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModerationInput {
+    /// Text.
+    Text {
+        /// The text.
+        text: String,
+    },
+    /// An image, by URL or as a base64 data URL. Only the omni moderation models accept images.
+    ImageUrl {
+        /// The image.
+        image_url: ImageUrl,
+    },
+}
+
+impl ModerationInput {
+    /// Moderate text.
+    pub fn text(text: impl Into<String>) -> Self {
+        ModerationInput::Text { text: text.into() }
+    }
+
+    /// Moderate an image. Use [`ImageUrl::from_path`] or [`ImageUrl::from_bytes`] for local images.
+    pub fn image(image_url: impl Into<ImageUrl>) -> Self {
+        ModerationInput::ImageUrl {
+            image_url: image_url.into(),
+        }
+    }
+}
+
+/// The result of moderating an input.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Moderation {
+    /// Whether the input was flagged in any category.
+    pub flagged: bool,
+    /// Whether the input was flagged in each category.
+    pub categories: Categories<bool>,
+    /// How confident the model is that the input falls in each category, between 0 and 1.
+    pub category_scores: Categories<f64>,
+    /// Which kinds of input (`text` or `image`) each category was applied to. Only reported by the omni moderation models.
+    #[serde(default)]
+    pub category_applied_input_types: Option<Categories<Vec<String>>>,
+}
+
+impl Moderation {
+    /// The categories the input was flagged in.
+    pub fn flagged_categories(&self) -> Vec<Category> {
+        Category::ALL
+            .into_iter()
+            .filter(|category| *self.categories.get(*category))
+            .collect()
+    }
+
+    /// The category with the highest score, along with its score.
+    pub fn top_category(&self) -> (Category, f64) {
+        Category::ALL
+            .into_iter()
+            .map(|category| (category, *self.category_scores.get(category)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap()
+    }
+}
+
+/// A category of harmful content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Content that expresses, incites, or promotes harassing language towards any target.
+    Harassment,
+    /// Harassment content that also includes violence or serious harm towards any target.
+    HarassmentThreatening,
+    /// Content that expresses, incites, or promotes hate based on a protected attribute.
+    Hate,
+    /// Hateful content that also includes violence or serious harm towards the targeted group.
+    HateThreatening,
+    /// Content that gives advice or instruction on how to commit illicit acts.
+    Illicit,
+    /// Illicit content that also includes references to violence, or procuring a weapon.
+    IllicitViolent,
+    /// Content that promotes, encourages, or depicts acts of self-harm.
+    SelfHarm,
+    /// Content where the speaker expresses that they are engaging or intend to engage in acts of self-harm.
+    SelfHarmIntent,
+    /// Content that encourages performing acts of self-harm, or that gives instructions on how to do so.
+    SelfHarmInstructions,
+    /// Content meant to arouse sexual excitement, or that promotes sexual services.
+    Sexual,
+    /// Sexual content that includes an individual who is under 18 years old.
+    SexualMinors,
+    /// Content that depicts death, violence, or physical injury.
+    Violence,
+    /// Content that depicts death, violence, or physical injury in graphic detail.
+    ViolenceGraphic,
+}
+
+impl Category {
+    /// All the categories.
+    pub const ALL: [Category; 13] = [
+        Category::Harassment,
+        Category::HarassmentThreatening,
+        Category::Hate,
+        Category::HateThreatening,
+        Category::Illicit,
+        Category::IllicitViolent,
+        Category::SelfHarm,
+        Category::SelfHarmIntent,
+        Category::SelfHarmInstructions,
+        Category::Sexual,
+        Category::SexualMinors,
+        Category::Violence,
+        Category::ViolenceGraphic,
+    ];
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Category::Harassment => write!(f, "harassment"),
+            Category::HarassmentThreatening => write!(f, "harassment/threatening"),
+            Category::Hate => write!(f, "hate"),
+            Category::HateThreatening => write!(f, "hate/threatening"),
+            Category::Illicit => write!(f, "illicit"),
+            Category::IllicitViolent => write!(f, "illicit/violent"),
+            Category::SelfHarm => write!(f, "self-harm"),
+            Category::SelfHarmIntent => write!(f, "self-harm/intent"),
+            Category::SelfHarmInstructions => write!(f, "self-harm/instructions"),
+            Category::Sexual => write!(f, "sexual"),
+            Category::SexualMinors => write!(f, "sexual/minors"),
+            Category::Violence => write!(f, "violence"),
+            Category::ViolenceGraphic => write!(f, "violence/graphic"),
+        }
+    }
+}
+
+/// A value for each [`Category`]: whether the input was flagged in it, or its score.
+///
+/// Categories that the model doesn't support (such as `illicit` for the legacy text moderation models) have the default value.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + Default"))]
+pub struct Categories<T> {
+    /// [`Category::Harassment`].
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub harassment: T,
+    /// [`Category::HarassmentThreatening`].
+    #[serde(
+        rename = "harassment/threatening",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub harassment_threatening: T,
+    /// [`Category::Hate`].
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub hate: T,
+    /// [`Category::HateThreatening`].
+    #[serde(
+        rename = "hate/threatening",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub hate_threatening: T,
+    /// [`Category::Illicit`].
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub illicit: T,
+    /// [`Category::IllicitViolent`].
+    #[serde(
+        rename = "illicit/violent",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub illicit_violent: T,
+    /// [`Category::SelfHarm`].
+    #[serde(rename = "self-harm", default, deserialize_with = "null_as_default")]
+    pub self_harm: T,
+    /// [`Category::SelfHarmIntent`].
+    #[serde(
+        rename = "self-harm/intent",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub self_harm_intent: T,
+    /// [`Category::SelfHarmInstructions`].
+    #[serde(
+        rename = "self-harm/instructions",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub self_harm_instructions: T,
+    /// [`Category::Sexual`].
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub sexual: T,
+    /// [`Category::SexualMinors`].
+    #[serde(
+        rename = "sexual/minors",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub sexual_minors: T,
+    /// [`Category::Violence`].
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub violence: T,
+    /// [`Category::ViolenceGraphic`].
+    #[serde(
+        rename = "violence/graphic",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub violence_graphic: T,
+}
+
+impl<T> Categories<T> {
+    /// The value for `category`.
+    pub fn get(&self, category: Category) -> &T {
+        match category {
+            Category::Harassment => &self.harassment,
+            Category::HarassmentThreatening => &self.harassment_threatening,
+            Category::Hate => &self.hate,
+            Category::HateThreatening => &self.hate_threatening,
+            Category::Illicit => &self.illicit,
+            Category::IllicitViolent => &self.illicit_violent,
+            Category::SelfHarm => &self.self_harm,
+            Category::SelfHarmIntent => &self.self_harm_intent,
+            Category::SelfHarmInstructions => &self.self_harm_instructions,
+            Category::Sexual => &self.sexual,
+            Category::SexualMinors => &self.sexual_minors,
+            Category::Violence => &self.violence,
+            Category::ViolenceGraphic => &self.violence_graphic,
+        }
+    }
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// A client for interacting with the OpenAI Moderation API.
+///
+/// Moderation is free, so requests are not recorded in a [`UsageLedger`](crate::usage::UsageLedger).
+pub struct ModerationClient {
+    /// The API key to use for the Moderation API.
+    pub api_key: String,
+    /// The URL of the API. Customize this if you are using a custom API that is compatible with OpenAI's.
+    pub base_url: url::Url,
+    /// The subpath to the moderations endpoint. By default, this is `moderations`.
+    pub moderations_path: String,
+    /// The model to use. By default, this is [`DEFAULT_MODERATION_MODEL`].
+    pub model: String,
+    /// How requests are authenticated. By default, this is [`Authentication::Bearer`].
+    pub authentication: Authentication,
+}
+
+/// Errors that can occur when interacting with the Moderation API.
+#[derive(Error, Debug)]
+pub enum ModerationError {
+    /// An error occurred when sending the request to the API.
+    #[error("Request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    /// An error occurred when deserializing the response from the API.
+    #[error("API {url} returned an unknown response: {response}")]
+    ApiParseError {
+        /// The URL of the API that returned the error.
+        url: String,
+        /// The response from the API.
+        response: String,
+        /// The error that occurred when deserializing the response.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// The API returned an error.
+    #[error("API returned an error response")]
+    ApiError(#[source] OpenAiError),
+
+    /// The API returned a different number of results than there were inputs.
+    #[error("The wrong amount of moderation results was returned from API")]
+    IncorrectNumberOfResults,
+}
+
+impl ModerationClient {
+    /// Create a new [`ModerationClient`], which uses [`DEFAULT_MODERATION_MODEL`].
+    /// If the API key is in the environment, you can use the [`Self::from_env`] method instead.
+    ///
+    /// ```rust
+    /// use tysm::moderation::ModerationClient;
+    ///
+    /// let client = ModerationClient::new("sk-1234567890");
+    /// ```
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: "https://api.openai.com/v1/".parse().unwrap(),
+            moderations_path: "moderations".into(),
+            model: DEFAULT_MODERATION_MODEL.into(),
+            authentication: Authentication::Bearer,
+        }
+    }
+
+    /// Create a new [`ModerationClient`].
+    /// This will use the `OPENAI_API_KEY` environment variable to set the API key.
+    /// It will also look in the `.env` file for an `OPENAI_API_KEY` variable (using dotenv).
+    ///
+    /// ```rust
+    /// # use tysm::moderation::ModerationClient;
+    /// let client = ModerationClient::from_env().unwrap();
+    /// ```
+    pub fn from_env() -> Result<Self, OpenAiApiKeyError> {
+        Ok(Self::new(api_key()?))
+    }
+
+    /// Sets the model, such as `text-moderation-latest`.
+    pub fn with_model(self, model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            ..self
+        }
+    }
+
+    /// Sets the base URL
+    ///
+    /// Panics if the argument is not a valid URL.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = base_url(url);
+        self
+    }
+
+    /// Sets the path to the moderations endpoint.
+    ///
+    /// By default, this is `moderations`.
+    pub fn with_path(self, path: impl Into<String>) -> Self {
+        Self {
+            moderations_path: path.into(),
+            ..self
+        }
+    }
+
+    fn moderations_url(&self) -> url::Url {
+        self.base_url.join(&self.moderations_path).unwrap()
+    }
+
+    /// Moderate a single text.
+    pub async fn moderate_single(
+        &self,
+        text: impl Into<String>,
+    ) -> Result<Moderation, ModerationError> {
+        let mut moderations = self.moderate(&[text.into()]).await?;
+        Ok(moderations.remove(0))
+    }
+
+    /// Moderate several texts. Returns one result per text, in the same order.
+    pub async fn moderate(&self, texts: &[String]) -> Result<Vec<Moderation>, ModerationError> {
+        let moderations = self.send(texts).await?;
+        if moderations.len() != texts.len() {
+            return Err(ModerationError::IncorrectNumberOfResults);
+        }
+        Ok(moderations)
+    }
+
+    /// Moderate text and images together. The omni moderation models return a single result for all the inputs,
+    /// which says which kinds of input each category applied to (see [`Moderation::category_applied_input_types`]).
+    ///
+    /// ```rust,no_run
+    /// # use tysm::moderation::{ModerationClient, ModerationInput};
+    /// # use tysm::chat_completions::ImageUrl;
+    /// # tokio_test::block_on(async {
+    /// let client = ModerationClient::from_env().unwrap();
+    /// let moderation = client
+    ///     .moderate_inputs(&[
+    ///         ModerationInput::text("What is in this picture?"),
+    ///         ModerationInput::image(ImageUrl::from_path("picture.png").unwrap()),
+    ///     ])
+    ///     .await
+    ///     .unwrap();
+    /// # })
+    /// ```
+    pub async fn moderate_inputs(
+        &self,
+        inputs: &[ModerationInput],
+    ) -> Result<Moderation, ModerationError> {
+        self.send(inputs)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(ModerationError::IncorrectNumberOfResults)
+    }
+
+    async fn send<I: Serialize>(&self, input: &[I]) -> Result<Vec<Moderation>, ModerationError> {
+        let request = ModerationRequest {
+            model: &self.model,
+            input,
+        };
+        let response = Client::new()
+            .post(self.moderations_url())
+            .authenticate(&self.authentication, &self.api_key)
+            .json(&request)
+            .send()
+            .await?;
+        let response_text = response.text().await?;
+
+        let response: ModerationResponseOrError =
+            serde_json::from_str(&response_text).map_err(|e| ModerationError::ApiParseError {
+                url: self.moderations_url().to_string(),
+                response: response_text.clone(),
+                error: e,
+            })?;
+        match response {
+            ModerationResponseOrError::Response(response) => Ok(response.results),
+            ModerationResponseOrError::Error(error) => Err(ModerationError::ApiError(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_moderation() {
+        let response = r#"{
+            "id": "modr-1",
+            "model": "text-moderation-007",
+            "results": [{
+                "flagged": true,
+                "categories": {
+                    "sexual": false, "hate": false, "harassment": true, "self-harm": false,
+                    "sexual/minors": false, "hate/threatening": false, "violence/graphic": false,
+                    "self-harm/intent": false, "self-harm/instructions": false,
+                    "harassment/threatening": true, "violence": true, "illicit": null, "illicit/violent": null
+                },
+                "category_scores": {
+                    "sexual": 0.0001, "hate": 0.002, "harassment": 0.8, "self-harm": 0.0,
+                    "sexual/minors": 0.0, "hate/threatening": 0.0001, "violence/graphic": 0.0,
+                    "self-harm/intent": 0.0, "self-harm/instructions": 0.0,
+                    "harassment/threatening": 0.9, "violence": 0.7, "illicit": null, "illicit/violent": null
+                }
+            }]
+        }"#;
+        let ModerationResponseOrError::Response(response) = serde_json::from_str(response).unwrap()
+        else {
+            panic!("expected a response");
+        };
+        let moderation = &response.results[0];
+        assert_eq!(
+            moderation.flagged_categories(),
+            vec![
+                Category::Harassment,
+                Category::HarassmentThreatening,
+                Category::Violence
+            ]
+        );
+        assert_eq!(
+            moderation.top_category(),
+            (Category::HarassmentThreatening, 0.9)
+        );
+        assert!(!moderation.categories.illicit);
+        assert_eq!(moderation.category_applied_input_types, None);
+
+        let input = serde_json::to_value([
+            ModerationInput::text("hello"),
+            ModerationInput::image(ImageUrl {
+                url: "https://example.com/cat.png".to_string(),
+                detail: None,
+            }),
+        ])
+        .unwrap();
+        assert_eq!(input[0]["type"], "text");
+        assert_eq!(input[1]["type"], "image_url");
+        assert_eq!(input[1]["image_url"]["url"], "https://example.com/cat.png");
+    }
+}
@@ -0,0 +1,81 @@
+//! Providers translate `tysm`'s chat requests into the wire format of a particular API.
+//!
+//! A [`ChatClient`](crate::chat_completions::ChatClient) sends every request through its provider, so the typed
+//! methods, caching, usage tracking and cost tracking work the same way regardless of which API is on the other end.
+//!
+//! The following providers are included:
+//! - [`OpenAiProvider`]: OpenAI's chat-completions API, and APIs compatible with it (Gemini, Ollama's `/v1` endpoint, ...)
+//! - [`AzureProvider`]: Azure OpenAI
+//! - [`AnthropicProvider`]: Anthropic's native Messages API
+//! - [`OllamaProvider`]: Ollama's native `/api/chat` endpoint
+//!
+//! You can implement [`ChatProvider`] yourself to support other APIs.
+
+use reqwest::{Client, RequestBuilder};
+
+use crate::chat_completions::{ChatRequest, ChatResponseOrError};
+use crate::utils::{Authenticate, Authentication};
+
+mod anthropic;
+mod azure;
+mod ollama;
+
+pub use anthropic::AnthropicProvider;
+pub use azure::AzureProvider;
+pub use ollama::OllamaProvider;
+
+/// Turns a provider-neutral [`ChatRequest`] into an HTTP request, and parses the provider's response.
+pub trait ChatProvider: std::fmt::Debug + Send + Sync {
+    /// Build the HTTP request for a chat request.
+    ///
+    /// `url` is the client's base URL joined with its `chat_completions_path`.
+    fn build_request(
+        &self,
+        http: &Client,
+        url: url::Url,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> RequestBuilder;
+
+    /// Parse the body of a response from the API.
+    ///
+    /// This is also used to parse cached responses, so it must accept anything the API returns.
+    fn parse_response(&self, response: &str) -> Result<ChatResponseOrError, serde_json::Error>;
+
+    /// Whether the provider supports OpenAI's batch API.
+    fn supports_batch(&self) -> bool {
+        false
+    }
+
+    /// How requests to the provider's other OpenAI-style endpoints (such as the files and batch APIs) are authenticated.
+    fn authentication(&self) -> Authentication {
+        Authentication::Bearer
+    }
+}
+
+/// OpenAI's chat-completions API, or any API that is compatible with it.
+#[derive(Debug, Default, Clone)]
+pub struct OpenAiProvider;
+
+impl ChatProvider for OpenAiProvider {
+    fn build_request(
+        &self,
+        http: &Client,
+        url: url::Url,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> RequestBuilder {
+        http.post(url)
+            .authenticate(&Authentication::Bearer, api_key)
+            .header("Content-Type", "application/json")
+            .json(request)
+    }
+
+    fn parse_response(&self, response: &str) -> Result<ChatResponseOrError, serde_json::Error> {
+        serde_json::from_str(response)
+    }
+
+    fn supports_batch(&self) -> bool {
+        true
+    }
+}
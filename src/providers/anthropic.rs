@@ -0,0 +1,422 @@
+//! Conversions between `tysm`'s chat types and Anthropic's [Messages API](https://docs.anthropic.com/en/api/messages).
+//!
+//! Structured outputs are implemented with forced tool use: the response schema becomes the input schema
+//! of a tool that the model is required to call.
+
+use log::warn;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ChatProvider;
+use crate::chat_completions::{
+    ChatChoice, ChatMessage, ChatMessageContent, ChatMessageResponse, ChatRequest, ChatResponse,
+    ChatResponseOrError, ChatUsage, PromptTokenDetails, ResponseFormat, Role,
+};
+use crate::OpenAiError;
+
+/// The default version of the Messages API that requests are made against.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Anthropic requires `max_tokens` to be set, so this is used when the request doesn't set it.
+/// 4096 is the lowest maximum among current Claude models.
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Tool input schemas must be objects, so other schemas are wrapped in an object under a `value` property.
+/// Tools with this name have their input unwrapped again when the response is parsed.
+const WRAPPED_VALUE_TOOL: &str = "respond_with_value";
+
+/// Anthropic's native [Messages API](https://docs.anthropic.com/en/api/messages).
+///
+/// Structured outputs are implemented by forcing the model to call a tool whose input schema is the response schema.
+#[derive(Debug, Clone)]
+pub struct AnthropicProvider {
+    /// The value of the `anthropic-version` header.
+    pub version: String,
+}
+
+impl Default for AnthropicProvider {
+    fn default() -> Self {
+        Self {
+            version: ANTHROPIC_VERSION.to_string(),
+        }
+    }
+}
+
+impl ChatProvider for AnthropicProvider {
+    fn build_request(
+        &self,
+        http: &Client,
+        url: url::Url,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> RequestBuilder {
+        http.post(url)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", &self.version)
+            .header("Content-Type", "application/json")
+            .json(&MessagesRequest::from(request))
+    }
+
+    fn parse_response(&self, response: &str) -> Result<ChatResponseOrError, serde_json::Error> {
+        let response: MessagesResponseOrError = serde_json::from_str(response)?;
+        Ok(response.into())
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct MessagesRequest {
+    model: String,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
+}
+
+#[derive(Serialize, Debug)]
+struct Message {
+    role: &'static str,
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: ImageSource,
+    },
+    Document {
+        source: ImageSource,
+    },
+    ToolUse {
+        #[serde(default)]
+        id: String,
+        name: String,
+        input: Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
+}
+
+#[derive(Serialize, Debug)]
+struct Tool {
+    name: String,
+    description: String,
+    input_schema: Value,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ToolChoice {
+    Tool { name: String },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MessagesResponseOrError {
+    Error { error: AnthropicError },
+    Message(MessagesResponse),
+}
+
+#[derive(Deserialize, Debug)]
+struct AnthropicError {
+    r#type: String,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct MessagesResponse {
+    id: String,
+    model: String,
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    usage: MessagesUsage,
+}
+
+#[derive(Deserialize, Debug)]
+struct MessagesUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+    #[serde(default)]
+    cache_creation_input_tokens: Option<u32>,
+    #[serde(default)]
+    cache_read_input_tokens: Option<u32>,
+}
+
+impl From<&ChatRequest> for MessagesRequest {
+    fn from(request: &ChatRequest) -> Self {
+        let mut system = Vec::new();
+        let mut messages = Vec::new();
+        for message in &request.messages {
+            match message.role {
+                Role::System | Role::Developer => {
+                    system.extend(message.content.iter().filter_map(|part| match part {
+                        ChatMessageContent::Text { text } if !text.trim().is_empty() => {
+                            Some(text.clone())
+                        }
+                        _ => None,
+                    }))
+                }
+                Role::User => messages.push(Message::new("user", message)),
+                Role::Assistant => messages.push(Message::new("assistant", message)),
+            }
+        }
+
+        let (tools, tool_choice) = match &request.response_format {
+            ResponseFormat::JsonSchema { json_schema } => {
+                let schema = serde_json::to_value(&json_schema.schema).unwrap();
+                let (name, input_schema) = if crate::schema::is_object_schema(&schema) {
+                    (tool_name(&json_schema.name), schema)
+                } else {
+                    (
+                        WRAPPED_VALUE_TOOL.to_string(),
+                        crate::schema::wrap_schema(schema),
+                    )
+                };
+                let tool = Tool {
+                    name: name.clone(),
+                    description: "Respond to the user with a value matching this schema."
+                        .to_string(),
+                    input_schema,
+                };
+                (vec![tool], Some(ToolChoice::Tool { name }))
+            }
+            ResponseFormat::JsonObject | ResponseFormat::Text => (vec![], None),
+        };
+
+        Self {
+            model: request.model.clone(),
+            max_tokens: request.output_limit().unwrap_or(DEFAULT_MAX_TOKENS),
+            system: (!system.is_empty()).then(|| system.join("\n\n")),
+            messages,
+            tools,
+            tool_choice,
+        }
+    }
+}
+
+impl Message {
+    fn new(role: &'static str, message: &ChatMessage) -> Self {
+        let content = message
+            .content
+            .iter()
+            .filter_map(|part| match part {
+                ChatMessageContent::Text { text } => {
+                    Some(ContentBlock::Text { text: text.clone() })
+                }
+                ChatMessageContent::ImageUrl { image } => {
+                    let source = match image
+                        .url
+                        .strip_prefix("data:")
+                        .and_then(|data| data.split_once(";base64,"))
+                    {
+                        Some((media_type, data)) => ImageSource::Base64 {
+                            media_type: media_type.to_string(),
+                            data: data.to_string(),
+                        },
+                        None => ImageSource::Url {
+                            url: image.url.clone(),
+                        },
+                    };
+                    Some(ContentBlock::Image { source })
+                }
+                ChatMessageContent::File { file } => {
+                    match file
+                        .file_data
+                        .as_deref()
+                        .and_then(|data| data.strip_prefix("data:"))
+                        .and_then(|data| data.split_once(";base64,"))
+                    {
+                        Some((media_type, data)) => Some(ContentBlock::Document {
+                            source: ImageSource::Base64 {
+                                media_type: media_type.to_string(),
+                                data: data.to_string(),
+                            },
+                        }),
+                        None => {
+                            warn!("Anthropic only accepts inline files, skipping {file:?}");
+                            None
+                        }
+                    }
+                }
+                ChatMessageContent::InputAudio { .. } => {
+                    warn!("Anthropic doesn't accept audio, skipping it");
+                    None
+                }
+            })
+            .collect();
+        Self { role, content }
+    }
+}
+
+/// Tool names must match `^[a-zA-Z0-9_-]{1,64}$`.
+fn tool_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect::<String>();
+    if name.is_empty() || name == WRAPPED_VALUE_TOOL {
+        "response".to_string()
+    } else {
+        name
+    }
+}
+
+impl From<MessagesResponseOrError> for ChatResponseOrError {
+    fn from(response: MessagesResponseOrError) -> Self {
+        match response {
+            MessagesResponseOrError::Error { error } => ChatResponseOrError::Error(OpenAiError {
+                r#type: error.r#type,
+                code: None,
+                message: error.message,
+                param: None,
+            }),
+            MessagesResponseOrError::Message(message) => {
+                ChatResponseOrError::Response(message.into())
+            }
+        }
+    }
+}
+
+impl From<MessagesResponse> for ChatResponse {
+    fn from(response: MessagesResponse) -> Self {
+        let tool_input = response.content.iter().find_map(|block| match block {
+            ContentBlock::ToolUse { name, input, .. } if name == WRAPPED_VALUE_TOOL => {
+                Some(input.get("value").cloned().unwrap_or(Value::Null))
+            }
+            ContentBlock::ToolUse { input, .. } => Some(input.clone()),
+            _ => None,
+        });
+        let content = match tool_input {
+            Some(input) => input.to_string(),
+            None => response
+                .content
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        let stop_reason = response.stop_reason.unwrap_or_default();
+        let refusal = (stop_reason == "refusal").then(|| {
+            if content.trim().is_empty() {
+                "The model refused to respond.".to_string()
+            } else {
+                content.clone()
+            }
+        });
+
+        let usage = response.usage;
+        let cache_read_input_tokens = usage.cache_read_input_tokens.unwrap_or_default();
+        let cache_creation_input_tokens = usage.cache_creation_input_tokens.unwrap_or_default();
+        let prompt_tokens =
+            usage.input_tokens + cache_creation_input_tokens + cache_read_input_tokens;
+
+        ChatResponse {
+            id: response.id,
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: response.model,
+            system_fingerprint: None,
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatMessageResponse {
+                    role: Role::Assistant,
+                    content: Some(content),
+                    refusal,
+                },
+                logprobs: None,
+                finish_reason: stop_reason,
+            }],
+            usage: ChatUsage {
+                prompt_tokens,
+                completion_tokens: usage.output_tokens,
+                total_tokens: prompt_tokens + usage.output_tokens,
+                prompt_token_details: Some(PromptTokenDetails {
+                    cached_tokens: cache_read_input_tokens,
+                    cache_write_tokens: cache_creation_input_tokens,
+                    ..PromptTokenDetails::default()
+                }),
+                completion_token_details: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_completions::JsonSchemaFormat;
+
+    #[test]
+    fn structured_outputs_use_forced_tool_use() {
+        #[derive(schemars::JsonSchema)]
+        #[expect(unused)]
+        enum Sentiment {
+            Positive,
+            Negative,
+        }
+
+        let request = ChatRequest::new(
+            "claude-sonnet-4-20250514",
+            vec![
+                ChatMessage::system("You are a sentiment classifier."),
+                ChatMessage::user("I love it!"),
+            ],
+            ResponseFormat::JsonSchema {
+                json_schema: JsonSchemaFormat::new::<Sentiment>(),
+            },
+        );
+        let request = serde_json::to_value(MessagesRequest::from(&request)).unwrap();
+        assert_eq!(request["system"], "You are a sentiment classifier.");
+        assert_eq!(request["messages"][0]["role"], "user");
+        assert_eq!(request["tool_choice"]["name"], WRAPPED_VALUE_TOOL);
+        assert_eq!(request["tools"][0]["input_schema"]["type"], "object");
+
+        let response = r#"{
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [{"type": "tool_use", "id": "toolu_01", "name": "respond_with_value", "input": {"value": "Positive"}}],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 400, "output_tokens": 20, "cache_read_input_tokens": 100, "cache_creation_input_tokens": 50}
+        }"#;
+        let ChatResponseOrError::Response(response) = AnthropicProvider::default()
+            .parse_response(response)
+            .unwrap()
+        else {
+            panic!("Expected a response");
+        };
+        let message = response.choices[0].message.clone();
+        assert_eq!(message.content().unwrap(), r#""Positive""#);
+        assert_eq!(response.usage.prompt_tokens, 550);
+        assert_eq!(response.usage.total_tokens, 570);
+        let details = response.usage.prompt_token_details.unwrap();
+        assert_eq!(details.cached_tokens, 100);
+        assert_eq!(details.cache_write_tokens, 50);
+    }
+}
@@ -0,0 +1,90 @@
+//! [Azure OpenAI](https://learn.microsoft.com/en-us/azure/ai-services/openai/reference) speaks OpenAI's wire format,
+//! but authenticates with an `api-key` header and requires an `api-version` query parameter.
+
+use reqwest::{Client, RequestBuilder};
+
+use super::ChatProvider;
+use crate::chat_completions::{ChatRequest, ChatResponseOrError};
+use crate::utils::{Authenticate, Authentication};
+
+/// Azure OpenAI. The deployment is part of the URL, so it is configured through the client's `chat_completions_path`.
+///
+/// You probably want to use [`ChatClient::azure`](crate::chat_completions::ChatClient::azure) rather than constructing this directly.
+#[derive(Debug, Clone)]
+pub struct AzureProvider {
+    /// The API version, such as `2024-10-21`.
+    pub api_version: String,
+}
+
+impl ChatProvider for AzureProvider {
+    fn build_request(
+        &self,
+        http: &Client,
+        url: url::Url,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> RequestBuilder {
+        http.post(url)
+            .authenticate(&self.authentication(), api_key)
+            .header("Content-Type", "application/json")
+            .json(request)
+    }
+
+    fn parse_response(&self, response: &str) -> Result<ChatResponseOrError, serde_json::Error> {
+        serde_json::from_str(response)
+    }
+
+    fn supports_batch(&self) -> bool {
+        true
+    }
+
+    fn authentication(&self) -> Authentication {
+        Authentication::Azure {
+            api_version: self.api_version.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chat_completions::{ChatClient, ChatMessage, ChatRequest, ResponseFormat};
+
+    #[test]
+    fn requests_use_deployment_url_and_api_key_header() {
+        let client = ChatClient::azure(
+            "https://my-resource.openai.azure.com",
+            "gpt-4o",
+            "2024-10-21",
+            "azure-key",
+        );
+        let request = ChatRequest::new(
+            client.model.clone(),
+            vec![ChatMessage::user("Hello!")],
+            ResponseFormat::Text,
+        );
+        let request = client
+            .provider
+            .build_request(
+                &reqwest::Client::new(),
+                client.base_url.join(&client.chat_completions_path).unwrap(),
+                &client.api_key,
+                &request,
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://my-resource.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(request.headers()["api-key"], "azure-key");
+        assert!(request.headers().get("Authorization").is_none());
+
+        let batch_client = crate::batch::BatchClient::from(&client);
+        assert_eq!(
+            batch_client.base_url.as_str(),
+            "https://my-resource.openai.azure.com/openai/"
+        );
+        assert_eq!(batch_client.endpoint, "/chat/completions");
+    }
+}
@@ -0,0 +1,238 @@
+//! Conversions between `tysm`'s chat types and Ollama's native [chat API](https://github.com/ollama/ollama/blob/main/docs/api.md#generate-a-chat-completion).
+//!
+//! Structured outputs are passed through Ollama's `format` parameter, which accepts a JSON schema.
+
+use log::warn;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::ChatProvider;
+use crate::chat_completions::{
+    ChatChoice, ChatMessage, ChatMessageContent, ChatMessageResponse, ChatRequest, ChatResponse,
+    ChatResponseOrError, ChatUsage, ResponseFormat, Role,
+};
+use crate::OpenAiError;
+
+/// Ollama's native `/api/chat` endpoint.
+///
+/// Unlike Ollama's OpenAI-compatible endpoint, this passes the response schema through to Ollama's
+/// `format` parameter, which constrains the model's output.
+#[derive(Debug, Default, Clone)]
+pub struct OllamaProvider;
+
+impl ChatProvider for OllamaProvider {
+    fn build_request(
+        &self,
+        http: &Client,
+        url: url::Url,
+        _api_key: &str,
+        request: &ChatRequest,
+    ) -> RequestBuilder {
+        http.post(url)
+            .header("Content-Type", "application/json")
+            .json(&OllamaChatRequest::from(request))
+    }
+
+    fn parse_response(&self, response: &str) -> Result<ChatResponseOrError, serde_json::Error> {
+        let response: OllamaResponseOrError = serde_json::from_str(response)?;
+        Ok(response.into())
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<OllamaOptions>,
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaOptions {
+    num_predict: u32,
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaMessage {
+    role: Role,
+    content: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum OllamaResponseOrError {
+    Error { error: String },
+    Response(OllamaResponse),
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaResponse {
+    model: String,
+    message: OllamaResponseMessage,
+    #[serde(default)]
+    done_reason: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: u32,
+    #[serde(default)]
+    eval_count: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaResponseMessage {
+    content: String,
+}
+
+impl From<&ChatRequest> for OllamaChatRequest {
+    fn from(request: &ChatRequest) -> Self {
+        let format = match &request.response_format {
+            ResponseFormat::JsonSchema { json_schema } => {
+                Some(serde_json::to_value(&json_schema.schema).unwrap())
+            }
+            ResponseFormat::JsonObject => Some(Value::String("json".to_string())),
+            ResponseFormat::Text => None,
+        };
+
+        Self {
+            model: request.model.clone(),
+            messages: request.messages.iter().map(OllamaMessage::from).collect(),
+            stream: false,
+            format,
+            options: request
+                .output_limit()
+                .map(|num_predict| OllamaOptions { num_predict }),
+        }
+    }
+}
+
+impl From<&ChatMessage> for OllamaMessage {
+    fn from(message: &ChatMessage) -> Self {
+        let mut content = Vec::new();
+        let mut images = Vec::new();
+        for part in &message.content {
+            match part {
+                ChatMessageContent::Text { text } => content.push(text.as_str()),
+                ChatMessageContent::ImageUrl { image } => match image.url.split_once(";base64,") {
+                    Some((_, data)) => images.push(data.to_string()),
+                    None => warn!(
+                        "Ollama only accepts base64-encoded images, skipping {}",
+                        image.url
+                    ),
+                },
+                ChatMessageContent::InputAudio { .. } | ChatMessageContent::File { .. } => {
+                    warn!("Ollama doesn't accept audio or files, skipping them")
+                }
+            }
+        }
+
+        Self {
+            // Ollama doesn't know the developer role, which is the system role under another name
+            role: match message.role {
+                Role::Developer => Role::System,
+                role => role,
+            },
+            content: content.join("\n"),
+            images,
+        }
+    }
+}
+
+impl From<OllamaResponseOrError> for ChatResponseOrError {
+    fn from(response: OllamaResponseOrError) -> Self {
+        match response {
+            OllamaResponseOrError::Error { error } => ChatResponseOrError::Error(OpenAiError {
+                r#type: "ollama_error".to_string(),
+                code: None,
+                message: error,
+                param: None,
+            }),
+            OllamaResponseOrError::Response(response) => {
+                ChatResponseOrError::Response(response.into())
+            }
+        }
+    }
+}
+
+impl From<OllamaResponse> for ChatResponse {
+    fn from(response: OllamaResponse) -> Self {
+        ChatResponse {
+            id: String::new(),
+            object: "chat.completion".to_string(),
+            created: 0,
+            model: response.model,
+            system_fingerprint: None,
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatMessageResponse {
+                    role: Role::Assistant,
+                    content: Some(response.message.content),
+                    refusal: None,
+                },
+                logprobs: None,
+                finish_reason: response.done_reason.unwrap_or_default(),
+            }],
+            usage: ChatUsage {
+                prompt_tokens: response.prompt_eval_count,
+                completion_tokens: response.eval_count,
+                total_tokens: response.prompt_eval_count + response.eval_count,
+                prompt_token_details: None,
+                completion_token_details: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_completions::JsonSchemaFormat;
+
+    #[test]
+    fn schema_is_passed_as_format() {
+        #[derive(schemars::JsonSchema)]
+        #[expect(unused)]
+        struct Name {
+            first: String,
+            last: String,
+        }
+
+        let request = ChatRequest::new(
+            "llama3.2",
+            vec![ChatMessage::user("Who was the first US president?")],
+            ResponseFormat::JsonSchema {
+                json_schema: JsonSchemaFormat::new::<Name>(),
+            },
+        );
+        let request = serde_json::to_value(OllamaChatRequest::from(&request)).unwrap();
+        assert_eq!(request["stream"], false);
+        assert_eq!(request["messages"][0]["role"], "user");
+        assert_eq!(request["format"]["type"], "object");
+        assert_eq!(request["format"]["required"][0], "first");
+
+        let response = r#"{
+            "model": "llama3.2",
+            "created_at": "2024-12-06T18:20:00Z",
+            "message": {"role": "assistant", "content": "{\"first\": \"George\", \"last\": \"Washington\"}"},
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 30,
+            "eval_count": 12
+        }"#;
+        let ChatResponseOrError::Response(response) =
+            OllamaProvider.parse_response(response).unwrap()
+        else {
+            panic!("Expected a response");
+        };
+        assert_eq!(response.usage.total_tokens, 42);
+
+        let error = OllamaProvider
+            .parse_response(r#"{"error": "model \"llama3.2\" not found"}"#)
+            .unwrap();
+        assert!(matches!(error, ChatResponseOrError::Error(_)));
+    }
+}
@@ -0,0 +1,957 @@
+//! A client for OpenAI's [Responses API](https://platform.openai.com/docs/api-reference/responses), the successor of the chat-completions API.
+//!
+//! Like [`ChatClient`](crate::chat_completions::ChatClient), [`ResponsesClient`] returns typed values (using Structured Outputs),
+//! caches responses, and records its usage in a [`UsageLedger`]. On top of that, the Responses API keeps the state of
+//! conversations on OpenAI's side (see [`ResponsesClient::follow_up`]), and has built-in tools such as web search.
+//!
+//! ```rust,no_run
+//! # tokio_test::block_on(async {
+//! use tysm::responses::ResponsesClient;
+//!
+//! #[derive(serde::Deserialize, schemars::JsonSchema)]
+//! struct Name {
+//!     first: String,
+//!     last: String,
+//! }
+//!
+//! let client = ResponsesClient::from_env("gpt-4.1").unwrap();
+//! let name: Name = client.respond("Who was the first US president?").await.unwrap();
+//! # })
+//! ```
+
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use log::{debug, warn};
+use lru::LruCache;
+use reqwest::Client;
+use schemars::{JsonSchema, Schema};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+use xxhash_rust::const_xxh3::xxh3_64 as const_xxh3;
+
+use crate::chat_completions::{
+    ChatMessage, ChatMessageContent, ChatUsage, CompletionTokenDetails, ImageDetail,
+    JsonSchemaFormat, PromptTokenDetails, ReasoningEffort, Role,
+};
+use crate::model_prices::PriceTable;
+use crate::models::{self, Capability, Unsupported};
+use crate::usage::{BudgetExceeded, Endpoint, UsageLedger, ASSUMED_MAX_COMPLETION_TOKENS};
+use crate::utils::{api_key, base_url, Authenticate, Authentication, OpenAiApiKeyError};
+use crate::OpenAiError;
+
+/// A client for the Responses API.
+pub struct ResponsesClient {
+    /// The API key to use for the Responses API.
+    pub api_key: String,
+    /// The URL of the API. Customize this if you are using a custom API that is compatible with OpenAI's.
+    pub base_url: url::Url,
+    /// The subpath to the responses endpoint. By default, this is `responses`.
+    pub responses_path: String,
+    /// The model to use.
+    pub model: String,
+    /// How requests are authenticated. By default, this is [`Authentication::Bearer`].
+    pub authentication: Authentication,
+    /// A cache of the few responses. Stores the last 1024 responses by default.
+    pub lru: RwLock<LruCache<String, String>>,
+    /// The directory in which to cache responses to requests.
+    pub cache_directory: Option<PathBuf>,
+    /// The ledger in which token consumption is recorded. This can be shared with other clients (see [`Self::with_ledger`]).
+    pub ledger: Arc<UsageLedger>,
+    /// The built-in tools the model may use. See [`Self::with_tool`].
+    pub tools: Vec<Tool>,
+    /// The maximum number of tokens the model may generate per request, including reasoning tokens.
+    pub max_output_tokens: Option<u32>,
+    /// How much reasoning models should think before answering.
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+/// A request to the Responses API. You probably will not need to use this directly, unless you are using [`ResponsesClient::respond_raw`].
+#[derive(Serialize, Debug, Clone)]
+pub struct ResponsesRequest {
+    /// The model to use.
+    pub model: String,
+    /// The input to the model.
+    pub input: Vec<InputItem>,
+    /// Instructions for the model, similar to a system prompt. Unlike a system message, they are not carried over
+    /// to follow-up responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// The ID of the response that this request follows up on. The earlier input and output are then part of the context.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_response_id: Option<String>,
+    /// The format of the text the model produces.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<TextConfig>,
+    /// The built-in tools the model may use.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Tool>,
+    /// The maximum number of tokens the model may generate, including reasoning tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    /// Options for reasoning models.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ReasoningConfig>,
+}
+
+/// An item of input. Build these from [`ChatMessage`]s with `InputItem::from`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputItem {
+    /// A message.
+    Message {
+        /// The role of the message's author.
+        role: Role,
+        /// The content of the message.
+        content: Vec<InputContent>,
+    },
+}
+
+/// A part of an input message.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputContent {
+    /// Text.
+    InputText {
+        /// The text.
+        text: String,
+    },
+    /// Text that the assistant produced earlier.
+    OutputText {
+        /// The text.
+        text: String,
+    },
+    /// An image.
+    InputImage {
+        /// The URL of the image, or a base64 data URL.
+        image_url: String,
+        /// The level of detail the image is looked at in.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        detail: Option<ImageDetail>,
+    },
+    /// A file.
+    InputFile {
+        /// The ID of an uploaded file.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file_id: Option<String>,
+        /// The file's contents, as a base64 data URL.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file_data: Option<String>,
+        /// The name of the file.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        filename: Option<String>,
+    },
+}
+
+impl From<ChatMessage> for InputItem {
+    fn from(message: ChatMessage) -> Self {
+        let role = message.role;
+        let content = message
+            .content
+            .into_iter()
+            .filter_map(|part| match part {
+                ChatMessageContent::Text { text } if role == Role::Assistant => {
+                    Some(InputContent::OutputText { text })
+                }
+                ChatMessageContent::Text { text } => Some(InputContent::InputText { text }),
+                ChatMessageContent::ImageUrl { image } => Some(InputContent::InputImage {
+                    image_url: image.url,
+                    detail: image.detail,
+                }),
+                ChatMessageContent::File { file } => Some(InputContent::InputFile {
+                    file_id: file.file_id,
+                    file_data: file.file_data,
+                    filename: file.filename,
+                }),
+                ChatMessageContent::InputAudio { .. } => {
+                    warn!("The Responses API doesn't accept audio, skipping it");
+                    None
+                }
+            })
+            .collect();
+        InputItem::Message { role, content }
+    }
+}
+
+/// The configuration of the text the model produces.
+#[derive(Serialize, Debug, Clone)]
+pub struct TextConfig {
+    /// The format of the text.
+    pub format: TextFormat,
+}
+
+/// The format of the text the model produces.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextFormat {
+    /// Plain text.
+    Text,
+    /// JSON matching a schema ([Structured Outputs](https://platform.openai.com/docs/guides/structured-outputs)).
+    JsonSchema(JsonSchemaFormat),
+    /// JSON, with no particular schema.
+    JsonObject,
+}
+
+/// Options for reasoning models.
+#[derive(Serialize, Debug, Clone)]
+pub struct ReasoningConfig {
+    /// How much the model should think before answering.
+    pub effort: ReasoningEffort,
+}
+
+/// A built-in tool that the model may use.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Tool {
+    /// Search the web.
+    WebSearchPreview {
+        /// How much context to retrieve from the web: `low`, `medium` (the default) or `high`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        search_context_size: Option<String>,
+    },
+    /// Search files that were uploaded to vector stores.
+    FileSearch {
+        /// The vector stores to search.
+        vector_store_ids: Vec<String>,
+        /// The maximum number of results to return.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_num_results: Option<u32>,
+    },
+    /// Run Python code in a sandbox.
+    CodeInterpreter {
+        /// The container the code runs in. `{"type": "auto"}` creates one automatically.
+        container: serde_json::Value,
+    },
+}
+
+impl Tool {
+    /// The web search tool, with the default settings.
+    pub fn web_search() -> Self {
+        Tool::WebSearchPreview {
+            search_context_size: None,
+        }
+    }
+
+    /// The file search tool, searching the given vector stores.
+    pub fn file_search(vector_store_ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Tool::FileSearch {
+            vector_store_ids: vector_store_ids.into_iter().map(Into::into).collect(),
+            max_num_results: None,
+        }
+    }
+
+    /// The code interpreter tool, in a container that is created automatically.
+    pub fn code_interpreter() -> Self {
+        Tool::CodeInterpreter {
+            container: serde_json::json!({"type": "auto"}),
+        }
+    }
+}
+
+/// A response from the Responses API.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Response {
+    /// The ID of the response. Pass it to [`ResponsesClient::follow_up`] to continue the conversation.
+    pub id: String,
+    /// The model that produced the response.
+    pub model: String,
+    /// The status of the response, such as `completed` or `incomplete`.
+    pub status: String,
+    /// The items the model produced: messages, and calls to built-in tools.
+    pub output: Vec<OutputItem>,
+    /// Why the response is incomplete, if it is.
+    #[serde(default)]
+    pub incomplete_details: Option<IncompleteDetails>,
+    /// The tokens used to produce the response.
+    #[serde(default)]
+    pub usage: Option<ResponseUsage>,
+}
+
+/// Why a response is incomplete.
+#[derive(Deserialize, Debug, Clone)]
+pub struct IncompleteDetails {
+    /// The reason, such as `max_output_tokens` or `content_filter`.
+    pub reason: String,
+}
+
+/// An item the model produced.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputItem {
+    /// A message.
+    Message {
+        /// The content of the message.
+        content: Vec<OutputContent>,
+    },
+    /// Another kind of item, such as a tool call or a reasoning summary.
+    #[serde(other)]
+    Other,
+}
+
+/// A part of an output message.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputContent {
+    /// Text.
+    OutputText {
+        /// The text.
+        text: String,
+        /// Citations and other annotations of the text.
+        #[serde(default)]
+        annotations: Vec<serde_json::Value>,
+    },
+    /// The model refused to respond.
+    Refusal {
+        /// The explanation of the refusal.
+        refusal: String,
+    },
+    /// Another kind of content.
+    #[serde(other)]
+    Other,
+}
+
+/// The token consumption of the Responses API.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub struct ResponseUsage {
+    /// The number of input tokens.
+    pub input_tokens: u32,
+    /// Details about the input tokens.
+    #[serde(default)]
+    pub input_tokens_details: Option<InputTokensDetails>,
+    /// The number of output tokens, including reasoning tokens.
+    pub output_tokens: u32,
+    /// Details about the output tokens.
+    #[serde(default)]
+    pub output_tokens_details: Option<OutputTokensDetails>,
+    /// The total number of tokens used.
+    pub total_tokens: u32,
+}
+
+/// Details about the input tokens of a response.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct InputTokensDetails {
+    /// The number of input tokens that were cached.
+    pub cached_tokens: u32,
+}
+
+/// Details about the output tokens of a response.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct OutputTokensDetails {
+    /// The number of tokens used for reasoning.
+    pub reasoning_tokens: u32,
+}
+
+impl From<ResponseUsage> for ChatUsage {
+    fn from(usage: ResponseUsage) -> Self {
+        ChatUsage {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: usage.total_tokens,
+            prompt_token_details: usage
+                .input_tokens_details
+                .map(|details| PromptTokenDetails {
+                    cached_tokens: details.cached_tokens,
+                    ..PromptTokenDetails::default()
+                }),
+            completion_token_details: usage.output_tokens_details.map(|details| {
+                CompletionTokenDetails {
+                    reasoning_tokens: details.reasoning_tokens,
+                    ..CompletionTokenDetails::default()
+                }
+            }),
+        }
+    }
+}
+
+impl Response {
+    /// The text of the response's messages.
+    pub fn output_text(&self) -> String {
+        self.output
+            .iter()
+            .filter_map(|item| match item {
+                OutputItem::Message { content } => Some(content),
+                OutputItem::Other => None,
+            })
+            .flatten()
+            .filter_map(|content| match content {
+                OutputContent::OutputText { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The refusal, if the model refused to respond.
+    pub fn refusal(&self) -> Option<&str> {
+        self.output
+            .iter()
+            .filter_map(|item| match item {
+                OutputItem::Message { content } => Some(content),
+                OutputItem::Other => None,
+            })
+            .flatten()
+            .find_map(|content| match content {
+                OutputContent::Refusal { refusal } => Some(refusal.as_str()),
+                _ => None,
+            })
+    }
+}
+
+/// A typed response, along with information about how it was produced.
+#[derive(Debug, Clone)]
+pub struct ResponseCompletion<T> {
+    /// The response.
+    pub value: T,
+    /// The ID of the response. Pass it to [`ResponsesClient::follow_up`] to continue the conversation.
+    pub id: String,
+    /// The model that produced the response.
+    pub model: String,
+    /// The tokens used to produce the response, as reported by the API.
+    pub usage: ChatUsage,
+    /// Whether the response came from the cache.
+    pub cached: bool,
+}
+
+/// The format for responses of type `T`, and whether the type had to be wrapped in an object (see [`Wrapped`]).
+fn structured_format<T: JsonSchema>() -> (JsonSchemaFormat, bool) {
+    let mut format = JsonSchemaFormat::new::<T>();
+    let schema = serde_json::to_value(&format.schema.schema).unwrap();
+    let wrapped = !crate::schema::is_object_schema(&schema);
+    if wrapped {
+        format.schema.schema = Schema::try_from(crate::schema::wrap_schema(schema)).unwrap();
+    }
+    (format, wrapped)
+}
+
+/// A value whose type isn't an object, wrapped in one, since Structured Outputs require the root of the schema to be an object.
+#[derive(Deserialize)]
+struct Wrapped<T> {
+    value: T,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ResponseOrError {
+    Error { error: OpenAiError },
+    Response(Response),
+}
+
+/// Errors that can occur when using the Responses API.
+#[derive(Error, Debug)]
+pub enum ResponsesError {
+    /// An error occurred when sending the request to the API.
+    #[error("Request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    /// The API returned a response that could not be parsed.
+    #[error("API returned a response that could not be parsed: `{response}`")]
+    ApiParseError {
+        /// The response from the API.
+        response: String,
+        /// The error that occurred when parsing the response.
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// The API returned an error.
+    #[error("API returned an error response for request {1}")]
+    ApiError(#[source] OpenAiError, String),
+
+    /// The response did not conform to the schema of the requested type.
+    #[error(
+        "API returned a response that did not conform to the given schema: `{0}` (response: `{1}`)"
+    )]
+    ResponseNotConformantToSchema(#[source] serde_json::Error, String),
+
+    /// The model refused to respond.
+    #[error("The API refused to fulfill the request: `{0}`")]
+    Refusal(String),
+
+    /// The response stopped before it was complete (for example, because it reached `max_output_tokens`).
+    #[error("The response is incomplete: {0}")]
+    Incomplete(String),
+
+    /// IO error (usually occurs when reading from or writing to the cache).
+    #[error("IO error")]
+    IoError(#[from] std::io::Error),
+
+    /// The request was not sent, because it could have pushed spending past the budget (see [`ResponsesClient::with_budget`]).
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetExceeded),
+
+    /// The request was not sent, because the model doesn't support a feature it uses (see [`crate::models`]).
+    #[error(transparent)]
+    Unsupported(#[from] Unsupported),
+}
+
+impl ResponsesRequest {
+    fn cache_key(&self) -> String {
+        let serialized = serde_json::to_string(&self).unwrap();
+        let id = const_xxh3(serialized.as_bytes());
+        format!("tysm-v1-responses_request-{}.zstd", id)
+    }
+
+    /// The most tokens this request could use, for checking it against a budget.
+    ///
+    /// Only the text of the input is counted, and the context of a previous response isn't known, so this underestimates follow-ups.
+    fn worst_case_usage(&self) -> ChatUsage {
+        let text = self
+            .input
+            .iter()
+            .flat_map(|InputItem::Message { content, .. }| content)
+            .filter_map(|part| match part {
+                InputContent::InputText { text } | InputContent::OutputText { text } => {
+                    Some(text.as_str())
+                }
+                _ => None,
+            })
+            .chain(self.instructions.as_deref())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt_tokens = crate::tokens::count_text(&self.model, &text);
+        let completion_tokens = self
+            .max_output_tokens
+            .unwrap_or(ASSUMED_MAX_COMPLETION_TOKENS);
+        ChatUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            ..ChatUsage::default()
+        }
+    }
+}
+
+impl ResponsesClient {
+    /// Create a new [`ResponsesClient`].
+    /// If the API key is in the environment, you can use the [`Self::from_env`] method instead.
+    ///
+    /// ```rust
+    /// use tysm::responses::ResponsesClient;
+    ///
+    /// let client = ResponsesClient::new("sk-1234567890", "gpt-4.1");
+    /// ```
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        use std::num::NonZeroUsize;
+
+        Self {
+            api_key: api_key.into(),
+            base_url: url::Url::parse("https://api.openai.com/v1/").unwrap(),
+            responses_path: "responses".to_string(),
+            model: model.into(),
+            authentication: Authentication::Bearer,
+            lru: RwLock::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
+            cache_directory: None,
+            ledger: Arc::new(UsageLedger::new()),
+            tools: Vec::new(),
+            max_output_tokens: None,
+            reasoning_effort: None,
+        }
+    }
+
+    /// Create a new [`ResponsesClient`].
+    /// This will use the `OPENAI_API_KEY` environment variable to set the API key.
+    /// It will also look in the `.env` file for an `OPENAI_API_KEY` variable (using dotenv).
+    pub fn from_env(model: impl Into<String>) -> Result<Self, OpenAiApiKeyError> {
+        Ok(Self::new(api_key()?, model))
+    }
+
+    /// Sets the base URL
+    ///
+    /// Panics if the argument is not a valid URL.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = base_url(url);
+        self
+    }
+
+    /// Set the cache directory for the client.
+    ///
+    /// The cache directory will be used to persistently cache all responses to requests.
+    pub fn with_cache_directory(mut self, cache_directory: impl Into<PathBuf>) -> Self {
+        let cache_directory = cache_directory.into();
+
+        if cache_directory.exists() && cache_directory.is_file() {
+            panic!("Cache directory is a file");
+        }
+
+        self.cache_directory = Some(cache_directory);
+        self
+    }
+
+    /// Record this client's usage in `ledger`, so that it can be shared with other clients.
+    pub fn with_ledger(mut self, ledger: Arc<UsageLedger>) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    /// Price this client's usage with `prices`. See [`ChatClient::with_prices`](crate::chat_completions::ChatClient::with_prices).
+    pub fn with_prices(self, prices: PriceTable) -> Self {
+        self.ledger.set_prices(prices);
+        self
+    }
+
+    /// Refuse to send requests once they could push spending past `dollars`.
+    ///
+    /// Like [`ChatClient::with_budget`](crate::chat_completions::ChatClient::with_budget), the budget is stored in the
+    /// client's ledger, and applies to every client that shares it.
+    pub fn with_budget(self, dollars: f64) -> Self {
+        self.ledger.set_budget(Some(dollars));
+        self
+    }
+
+    /// Let the model use a built-in tool.
+    ///
+    /// ```rust
+    /// use tysm::responses::{ResponsesClient, Tool};
+    ///
+    /// let client = ResponsesClient::new("sk-1234567890", "gpt-4.1").with_tool(Tool::web_search());
+    /// ```
+    pub fn with_tool(mut self, tool: Tool) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Set the maximum number of tokens the model may generate per request, including reasoning tokens.
+    pub fn with_max_output_tokens(mut self, max_output_tokens: u32) -> Self {
+        self.max_output_tokens = Some(max_output_tokens);
+        self
+    }
+
+    /// Set how much reasoning models should think before answering.
+    pub fn with_reasoning_effort(mut self, reasoning_effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(reasoning_effort);
+        self
+    }
+
+    fn responses_url(&self) -> url::Url {
+        self.base_url.join(&self.responses_path).unwrap()
+    }
+
+    /// Send a prompt, and get a value of type `T` back.
+    ///
+    /// ```rust,no_run
+    /// # use tysm::responses::ResponsesClient;
+    /// # tokio_test::block_on(async {
+    /// #[derive(serde::Deserialize, schemars::JsonSchema)]
+    /// struct CityName {
+    ///     english: String,
+    ///     local: String,
+    /// }
+    ///
+    /// let client = ResponsesClient::from_env("gpt-4.1").unwrap();
+    /// let city: CityName = client.respond("What is the capital of Portugal?").await.unwrap();
+    /// # })
+    /// ```
+    pub async fn respond<T: DeserializeOwned + JsonSchema>(
+        &self,
+        input: impl Into<String>,
+    ) -> Result<T, ResponsesError> {
+        Ok(self
+            .respond_with_messages_detailed(None, vec![ChatMessage::user(input)], None)
+            .await?
+            .value)
+    }
+
+    /// Send a prompt along with instructions (which play the role of a system prompt), and get a value of type `T` back.
+    pub async fn respond_with_instructions<T: DeserializeOwned + JsonSchema>(
+        &self,
+        instructions: impl Into<String>,
+        input: impl Into<String>,
+    ) -> Result<T, ResponsesError> {
+        Ok(self
+            .respond_with_messages_detailed(
+                Some(instructions.into()),
+                vec![ChatMessage::user(input)],
+                None,
+            )
+            .await?
+            .value)
+    }
+
+    /// Continue the conversation of an earlier response (by its [`ResponseCompletion::id`]). The earlier input and output
+    /// are kept by OpenAI, so they don't have to be sent again.
+    ///
+    /// ```rust,no_run
+    /// # use tysm::responses::ResponsesClient;
+    /// # tokio_test::block_on(async {
+    /// #[derive(serde::Deserialize, schemars::JsonSchema)]
+    /// struct Joke {
+    ///     setup: String,
+    ///     punchline: String,
+    /// }
+    ///
+    /// let client = ResponsesClient::from_env("gpt-4.1").unwrap();
+    /// let first = client
+    ///     .respond_with_messages_detailed::<Joke>(None, vec![tysm::chat_completions::ChatMessage::user("Tell me a joke.")], None)
+    ///     .await
+    ///     .unwrap();
+    /// // types that aren't objects (such as `String`) are wrapped in an object for the API, and unwrapped again
+    /// let explanation = client.follow_up::<String>(&first.id, "Explain it.").await.unwrap();
+    /// # })
+    /// ```
+    pub async fn follow_up<T: DeserializeOwned + JsonSchema>(
+        &self,
+        previous_response_id: &str,
+        input: impl Into<String>,
+    ) -> Result<ResponseCompletion<T>, ResponsesError> {
+        self.respond_with_messages_detailed(
+            None,
+            vec![ChatMessage::user(input)],
+            Some(previous_response_id),
+        )
+        .await
+    }
+
+    /// Send a sequence of messages (which can include images and files), optionally following up on an earlier response,
+    /// and get a value of type `T` back, along with the response's ID and usage.
+    pub async fn respond_with_messages_detailed<T: DeserializeOwned + JsonSchema>(
+        &self,
+        instructions: Option<String>,
+        messages: Vec<ChatMessage>,
+        previous_response_id: Option<&str>,
+    ) -> Result<ResponseCompletion<T>, ResponsesError> {
+        models::require(&self.model, Capability::StructuredOutputs)?;
+        if !self.tools.is_empty() {
+            models::require(&self.model, Capability::Tools)?;
+        }
+        let (format, wrapped) = structured_format::<T>();
+        let request = ResponsesRequest {
+            model: self.model.clone(),
+            input: models::adapt_roles(&self.model, messages)
+                .into_iter()
+                .map(InputItem::from)
+                .collect(),
+            instructions,
+            previous_response_id: previous_response_id.map(str::to_string),
+            text: Some(TextConfig {
+                format: TextFormat::JsonSchema(format),
+            }),
+            tools: self.tools.clone(),
+            max_output_tokens: self.max_output_tokens,
+            reasoning: self
+                .reasoning_effort
+                .map(|effort| ReasoningConfig { effort }),
+        };
+        let (response, cached) = self.send(&request).await?;
+
+        if let Some(refusal) = response.refusal() {
+            return Err(ResponsesError::Refusal(refusal.to_string()));
+        }
+        if let Some(details) = &response.incomplete_details {
+            return Err(ResponsesError::Incomplete(details.reason.clone()));
+        }
+        let text = response.output_text();
+        // the schema is enforced by the API, so the text is parsed as it is
+        let value = if wrapped {
+            serde_json::from_str::<Wrapped<T>>(&text).map(|wrapped| wrapped.value)
+        } else {
+            serde_json::from_str(&text)
+        }
+        .map_err(|e| ResponsesError::ResponseNotConformantToSchema(e, text.trim().to_string()))?;
+
+        Ok(ResponseCompletion {
+            value,
+            id: response.id,
+            model: response.model,
+            usage: response.usage.map(ChatUsage::from).unwrap_or_default(),
+            cached,
+        })
+    }
+
+    /// Send a request as it is, and get the whole response back.
+    pub async fn respond_raw(
+        &self,
+        request: &ResponsesRequest,
+    ) -> Result<Response, ResponsesError> {
+        Ok(self.send(request).await?.0)
+    }
+
+    /// Send a request (or get its response from the cache). Returns whether the response came from the cache.
+    async fn send(&self, request: &ResponsesRequest) -> Result<(Response, bool), ResponsesError> {
+        let request_str = serde_json::to_string(request).unwrap();
+        let (response, cached) = match self.cached(request).await {
+            Some(response) => {
+                debug!("Using cached response: {response}");
+                (response, true)
+            }
+            None => {
+                if request.reasoning.is_some() {
+                    models::require(&request.model, Capability::ReasoningEffort)?;
+                }
+                self.ledger.check_budget(
+                    &request.model,
+                    Endpoint::Responses,
+                    request.worst_case_usage(),
+                )?;
+                (self.uncached(request).await?, false)
+            }
+        };
+
+        let response = serde_json::from_str::<ResponseOrError>(&response).map_err(|e| {
+            ResponsesError::ApiParseError {
+                response: response.clone(),
+                error: e,
+            }
+        })?;
+        let response = match response {
+            ResponseOrError::Response(response) => response,
+            ResponseOrError::Error { error } => {
+                let request_str = if request_str.len() > 100 {
+                    request_str.chars().take(100).chain("...".chars()).collect()
+                } else {
+                    request_str
+                };
+                return Err(ResponsesError::ApiError(error, request_str));
+            }
+        };
+
+        if !cached {
+            if let Some(usage) = response.usage {
+                self.ledger
+                    .record(&request.model, Endpoint::Responses, usage.into());
+            }
+        }
+        Ok((response, cached))
+    }
+
+    async fn cached(&self, request: &ResponsesRequest) -> Option<String> {
+        let cache_key = request.cache_key();
+        let request = serde_json::to_string(request).ok()?;
+
+        if let Some(response) = self.lru.read().ok()?.peek(&request) {
+            return Some(response.clone());
+        }
+
+        let cache_directory = self.cache_directory.as_ref()?;
+        let compressed_data = tokio::fs::read(cache_directory.join(cache_key))
+            .await
+            .ok()?;
+        let decompressed_data = zstd::decode_all(compressed_data.as_slice()).ok()?;
+        String::from_utf8(decompressed_data).ok()
+    }
+
+    async fn uncached(&self, request: &ResponsesRequest) -> Result<String, ResponsesError> {
+        let response = Client::new()
+            .post(self.responses_url())
+            .authenticate(&self.authentication, &self.api_key)
+            .json(request)
+            .send()
+            .await?;
+        let status = response.status();
+        let response = response.text().await?;
+        debug!("Got response from API: {response}");
+
+        // incomplete responses and errors are not cached, so that they are retried next time
+        let completed = serde_json::from_str::<Response>(&response)
+            .is_ok_and(|response| response.status == "completed");
+        if status.is_success() && completed {
+            self.lru
+                .write()
+                .unwrap()
+                .put(serde_json::to_string(request).unwrap(), response.clone());
+
+            if let Some(cache_directory) = &self.cache_directory {
+                if !cache_directory.exists() {
+                    tokio::fs::create_dir_all(&cache_directory).await?;
+                }
+                let compressed = zstd::encode_all(response.as_bytes(), 3)?;
+                tokio::fs::write(cache_directory.join(request.cache_key()), compressed).await?;
+            }
+        }
+        Ok(response)
+    }
+
+    /// Attempts to compute the cost in dollars of the usage of this client.
+    ///
+    /// This is the cost of everything in the client's [ledger](Self::ledger), so if the ledger is shared with other clients,
+    /// their usage is included too. If the price of any of the models is unknown, this returns `None`.
+    pub fn cost(&self) -> Option<f64> {
+        self.ledger.cost()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_and_responses() {
+        #[derive(schemars::JsonSchema)]
+        #[expect(unused)]
+        struct Name {
+            first: String,
+            last: String,
+        }
+
+        let request = ResponsesRequest {
+            model: "gpt-4.1".to_string(),
+            input: vec![
+                ChatMessage::user("Who was the first US president?").into(),
+                ChatMessage::assistant("George Washington").into(),
+            ],
+            instructions: None,
+            previous_response_id: Some("resp_1".to_string()),
+            text: Some(TextConfig {
+                format: TextFormat::JsonSchema(JsonSchemaFormat::new::<Name>()),
+            }),
+            tools: vec![Tool::web_search()],
+            max_output_tokens: None,
+            reasoning: None,
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["input"][0]["type"], "message");
+        assert_eq!(json["input"][0]["content"][0]["type"], "input_text");
+        assert_eq!(json["input"][1]["content"][0]["type"], "output_text");
+        assert_eq!(json["text"]["format"]["type"], "json_schema");
+        assert_eq!(json["text"]["format"]["name"], "Name");
+        assert_eq!(json["tools"][0]["type"], "web_search_preview");
+        assert!(json.get("instructions").is_none());
+
+        let response = r#"{
+            "id": "resp_2",
+            "object": "response",
+            "model": "gpt-4.1-2025-04-14",
+            "status": "completed",
+            "output": [
+                {"type": "web_search_call", "id": "ws_1", "status": "completed"},
+                {"type": "message", "id": "msg_1", "role": "assistant", "content": [
+                    {"type": "output_text", "text": "{\"first\":\"George\",\"last\":\"Washington\"}", "annotations": []}
+                ]}
+            ],
+            "usage": {
+                "input_tokens": 100,
+                "input_tokens_details": {"cached_tokens": 40},
+                "output_tokens": 20,
+                "output_tokens_details": {"reasoning_tokens": 0},
+                "total_tokens": 120
+            }
+        }"#;
+        let ResponseOrError::Response(response) = serde_json::from_str(response).unwrap() else {
+            panic!("expected a response");
+        };
+        assert_eq!(
+            response.output_text(),
+            r#"{"first":"George","last":"Washington"}"#
+        );
+        let usage = ChatUsage::from(response.usage.unwrap());
+        assert_eq!(usage.prompt_token_details.unwrap().cached_tokens, 40);
+    }
+
+    #[test]
+    fn non_object_types_are_wrapped() {
+        let (format, wrapped) = structured_format::<String>();
+        assert!(wrapped);
+        let schema = serde_json::to_value(&format.schema).unwrap();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["value"]["type"], "string");
+        let value = crate::extract::extract_json::<Wrapped<String>>(r#"{"value": "hi"}"#).unwrap();
+        assert_eq!(value.value, "hi");
+
+        #[derive(JsonSchema)]
+        #[expect(unused)]
+        struct Name {
+            first: String,
+        }
+        assert!(!structured_format::<Name>().1);
+    }
+}
@@ -62,3 +62,24 @@ impl Transform for OpenAiTransform {
         transform_subschemas(self, schema);
     }
 }
+
+/// Whether the root of `schema` is an object, which Structured Outputs (and tool inputs) require.
+pub(crate) fn is_object_schema(schema: &Value) -> bool {
+    schema.get("type").and_then(Value::as_str) == Some("object")
+}
+
+/// Wrap a schema in an object, under a required `value` property.
+pub(crate) fn wrap_schema(mut schema: Value) -> Value {
+    // definitions are referenced from the root of the schema, so they have to stay there
+    let defs = schema.as_object_mut().and_then(|obj| obj.remove("$defs"));
+    let mut wrapped = serde_json::json!({
+        "type": "object",
+        "properties": { "value": schema },
+        "required": ["value"],
+        "additionalProperties": false,
+    });
+    if let Some(defs) = defs {
+        wrapped["$defs"] = defs;
+    }
+    wrapped
+}
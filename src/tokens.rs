@@ -0,0 +1,120 @@
+//! Counting tokens locally, before a request is sent.
+//!
+//! Prompts are tokenized with the same BPE vocabularies that OpenAI's models use (`o200k_base` for newer models
+//! such as `gpt-4o`, `gpt-4.1` and the `o` series, and `cl100k_base` for `gpt-4`, `gpt-3.5-turbo` and the embedding models).
+//! Other providers' models are counted with `o200k_base`, which is only an approximation of their tokenizers.
+//!
+//! ```rust
+//! use tysm::chat_completions::ChatMessage;
+//!
+//! let messages = vec![
+//!     ChatMessage::system("You are a helpful assistant."),
+//!     ChatMessage::user("Who was the first US president?"),
+//! ];
+//! let prompt_tokens = tysm::tokens::count("gpt-4o", &messages);
+//! ```
+
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+use crate::chat_completions::{ChatMessage, ChatMessageContent, ImageDetail};
+
+/// Every message is wrapped in a few tokens that mark where it starts and ends.
+const TOKENS_PER_MESSAGE: u32 = 3;
+
+/// A message's name takes up a token on top of the name's own tokens.
+const TOKENS_PER_NAME: u32 = 1;
+
+/// Every reply is primed with `<|start|>assistant<|message|>`.
+const TOKENS_PER_REPLY: u32 = 3;
+
+/// The number of tokens an image is assumed to take up. The real number depends on the image's
+/// size, which isn't known for image URLs. This is the cost of a 1024x1024 image at high detail.
+const TOKENS_PER_IMAGE: u32 = 765;
+
+/// Low-detail images always take up the same number of tokens.
+const TOKENS_PER_LOW_DETAIL_IMAGE: u32 = 85;
+
+fn encoding(model: &str) -> &'static CoreBPE {
+    match get_tokenizer(model) {
+        Some(Tokenizer::Cl100kBase) => tiktoken_rs::cl100k_base_singleton(),
+        _ => tiktoken_rs::o200k_base_singleton(),
+    }
+}
+
+/// Split `text` into tokens, using the tokenizer of `model`. This returns the tokens' IDs, as used by the `logit_bias` parameter.
+pub fn encode(model: &str, text: &str) -> Vec<u32> {
+    encoding(model).encode_with_special_tokens(text)
+}
+
+/// Count the tokens in `text`, using the tokenizer of `model`.
+pub fn count_text(model: &str, text: &str) -> u32 {
+    encoding(model).encode_with_special_tokens(text).len() as u32
+}
+
+/// Count the prompt tokens that sending `messages` to `model` will use.
+///
+/// This includes the tokens that the chat format adds around each message. It does not include the tokens
+/// used by a response format's schema, images are counted as a fixed number of tokens, and audio and files
+/// aren't counted at all, so the result is an estimate (though usually a close one for text).
+pub fn count(model: &str, messages: &[ChatMessage]) -> u32 {
+    let message_tokens = messages
+        .iter()
+        .map(|message| count_message(model, message))
+        .sum::<u32>();
+
+    message_tokens + TOKENS_PER_REPLY
+}
+
+/// Count the tokens a single message takes up in a prompt, including the tokens the chat format adds around it.
+///
+/// Unlike [`count`], this doesn't include the tokens that start the model's reply, so the counts of several messages can be added up.
+pub fn count_message(model: &str, message: &ChatMessage) -> u32 {
+    let encoding = encoding(model);
+    let count_text = |text: &str| encoding.encode_with_special_tokens(text).len() as u32;
+
+    let role = serde_json::to_value(message.role).unwrap();
+    let content_tokens = message
+        .content
+        .iter()
+        .map(|part| match part {
+            ChatMessageContent::Text { text } => count_text(text),
+            ChatMessageContent::ImageUrl { image } => match image.detail {
+                Some(ImageDetail::Low) => TOKENS_PER_LOW_DETAIL_IMAGE,
+                _ => TOKENS_PER_IMAGE,
+            },
+            // the size of audio and files depends on their contents, which can't be counted locally
+            ChatMessageContent::InputAudio { .. } | ChatMessageContent::File { .. } => 0,
+        })
+        .sum::<u32>();
+    let name_tokens = message
+        .name
+        .as_deref()
+        .map_or(0, |name| TOKENS_PER_NAME + count_text(name));
+    TOKENS_PER_MESSAGE
+        + count_text(role.as_str().unwrap_or_default())
+        + name_tokens
+        + content_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_chat_overhead() {
+        assert_eq!(count_text("gpt-4", "Hello world"), 2);
+        assert_eq!(count_text("gpt-4o", "Hello world"), 2);
+        // 3 for the message, 1 for the role, 2 for the content, and 3 for the reply
+        assert_eq!(count("gpt-4o", &[ChatMessage::user("Hello world")]), 9);
+        assert_eq!(count("gpt-4o", &[]), 3);
+        // 1 more for the name, and 1 for the name's token
+        assert_eq!(
+            count(
+                "gpt-4o",
+                &[ChatMessage::user("Hello world").with_name("alice")]
+            ),
+            11
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Tracking token consumption and cost across models and endpoints.
+//!
+//! Every client records the tokens it uses in a [`UsageLedger`]. By default each client has its own ledger,
+//! but a ledger can be shared between clients by passing the same `Arc` to each of them:
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use tysm::chat_completions::ChatClient;
+//! use tysm::embeddings::EmbeddingsClient;
+//! use tysm::usage::UsageLedger;
+//!
+//! let ledger = Arc::new(UsageLedger::new());
+//! let chat = ChatClient::new("sk-1234567890", "gpt-4o").with_ledger(ledger.clone());
+//! let embeddings = EmbeddingsClient::new("sk-1234567890", "text-embedding-3-small").with_ledger(ledger.clone());
+//!
+//! // ... use the clients ...
+//!
+//! println!("{}", ledger.to_csv());
+//! ```
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::chat_completions::ChatUsage;
+use crate::model_prices::PriceTable;
+
+/// The API through which tokens were used.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Endpoint {
+    /// The chat-completions API (or another provider's equivalent).
+    Chat,
+    /// Chat completions sent through the batch API, which are billed at a discount.
+    Batch,
+    /// The embeddings API.
+    Embeddings,
+    /// The Responses API.
+    Responses,
+    /// The image generation API.
+    Images,
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Chat => write!(f, "chat"),
+            Endpoint::Batch => write!(f, "batch"),
+            Endpoint::Embeddings => write!(f, "embeddings"),
+            Endpoint::Responses => write!(f, "responses"),
+            Endpoint::Images => write!(f, "images"),
+        }
+    }
+}
+
+/// The usage of one model through one endpoint.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LedgerEntry {
+    /// The model that was used.
+    pub model: String,
+    /// The endpoint the model was used through.
+    pub endpoint: Endpoint,
+    /// The number of requests that were made (not counting cached responses).
+    pub requests: u64,
+    /// The tokens that were used.
+    pub usage: ChatUsage,
+    /// The cost of the usage in dollars, if the price of the model is known.
+    pub cost: Option<f64>,
+}
+
+/// When a request doesn't set `max_tokens`, it is assumed to produce at most this many tokens
+/// when checking it against the budget.
+pub const ASSUMED_MAX_COMPLETION_TOKENS: u32 = 4096;
+
+/// A request was refused because it could have pushed spending past the budget.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Budget exceeded: ${spent:.4} of the ${budget:.4} budget has been spent, and the request could cost up to ${estimate:.4}")]
+pub struct BudgetExceeded {
+    /// The budget, in dollars.
+    pub budget: f64,
+    /// The amount that had been spent when the request was refused, in dollars.
+    pub spent: f64,
+    /// The worst-case cost of the refused request, in dollars.
+    pub estimate: f64,
+}
+
+/// A record of token consumption, keyed by model and by endpoint.
+///
+/// The ledger is thread-safe, and can be shared between clients through an `Arc` (see the [module documentation](self)).
+/// It can also hold a spending limit (see [`Self::set_budget`]), which is then enforced for every client that records into it.
+/// Usage is priced with the ledger's [`PriceTable`] (by default, the prices that ship with `tysm`).
+#[derive(Debug, Default)]
+pub struct UsageLedger {
+    entries: RwLock<HashMap<(String, Endpoint), (u64, ChatUsage)>>,
+    budget: RwLock<Option<f64>>,
+    prices: RwLock<PriceTable>,
+}
+
+impl UsageLedger {
+    /// Create an empty ledger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the tokens used by one request.
+    pub fn record(&self, model: impl Into<String>, endpoint: Endpoint, usage: ChatUsage) {
+        let mut entries = self.entries.write().unwrap();
+        let (requests, total) = entries.entry((model.into(), endpoint)).or_default();
+        *requests += 1;
+        *total += usage;
+    }
+
+    /// Replace the prices used to compute the cost of the ledger's usage.
+    pub fn set_prices(&self, prices: PriceTable) {
+        *self.prices.write().unwrap() = prices;
+    }
+
+    /// The prices used to compute the cost of the ledger's usage.
+    pub fn prices(&self) -> PriceTable {
+        self.prices.read().unwrap().clone()
+    }
+
+    /// Set the spending limit in dollars, or remove it with `None`.
+    ///
+    /// Before each request that isn't served from the cache, clients check that the ledger's [cost](Self::cost) plus the
+    /// worst-case cost of the request (estimated from the size of the prompt and `max_tokens`) stays within the budget.
+    /// Requests that would exceed it are refused with a [`BudgetExceeded`] error.
+    ///
+    /// The budget is a best-effort guard, not a hard limit:
+    /// - Usage is only recorded once a response arrives, so requests that are in flight at the same time are each
+    ///   checked against what was spent before any of them finished, and together they can overshoot the budget.
+    /// - Requests to models whose price is unknown are never refused, and their usage is not counted towards the budget.
+    pub fn set_budget(&self, budget: Option<f64>) {
+        *self.budget.write().unwrap() = budget;
+    }
+
+    /// The spending limit in dollars, if there is one.
+    pub fn budget(&self) -> Option<f64> {
+        *self.budget.read().unwrap()
+    }
+
+    /// Check whether a request with the given worst-case usage would fit in the remaining budget.
+    ///
+    /// Nothing is reserved, so this doesn't account for other requests that have been checked but not yet recorded
+    /// (see [`Self::set_budget`]).
+    pub fn check_budget(
+        &self,
+        model: &str,
+        endpoint: Endpoint,
+        worst_case: ChatUsage,
+    ) -> Result<(), BudgetExceeded> {
+        let Some(budget) = self.budget() else {
+            return Ok(());
+        };
+        let estimate = self
+            .prices
+            .read()
+            .unwrap()
+            .endpoint_cost(model, endpoint, worst_case);
+        let Some(estimate) = estimate else {
+            warn!("The price of {model} is unknown, so it can't be checked against the budget");
+            return Ok(());
+        };
+        let spent = self
+            .entries()
+            .iter()
+            .filter_map(|entry| entry.cost)
+            .sum::<f64>();
+        if spent + estimate > budget {
+            return Err(BudgetExceeded {
+                budget,
+                spent,
+                estimate,
+            });
+        }
+        Ok(())
+    }
+
+    /// Forget all recorded usage. The budget is kept.
+    pub fn reset(&self) {
+        self.entries.write().unwrap().clear();
+    }
+
+    /// All the entries in the ledger, sorted by model and then by endpoint.
+    pub fn entries(&self) -> Vec<LedgerEntry> {
+        let prices = self.prices.read().unwrap();
+        let entries = self.entries.read().unwrap();
+        let mut entries = entries
+            .iter()
+            .map(|((model, endpoint), (requests, usage))| LedgerEntry {
+                model: model.clone(),
+                endpoint: *endpoint,
+                requests: *requests,
+                usage: *usage,
+                cost: prices.endpoint_cost(model, *endpoint, *usage),
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| (&a.model, a.endpoint).cmp(&(&b.model, b.endpoint)));
+        entries
+    }
+
+    /// The total number of tokens used, across all models and endpoints.
+    pub fn usage(&self) -> ChatUsage {
+        let mut total = ChatUsage::default();
+        for (_, usage) in self.entries.read().unwrap().values() {
+            total += *usage;
+        }
+        total
+    }
+
+    /// The number of tokens used by each model, across all endpoints.
+    pub fn usage_by_model(&self) -> HashMap<String, ChatUsage> {
+        let mut by_model = HashMap::<String, ChatUsage>::new();
+        for ((model, _), (_, usage)) in self.entries.read().unwrap().iter() {
+            *by_model.entry(model.clone()).or_default() += *usage;
+        }
+        by_model
+    }
+
+    /// The number of tokens used through each endpoint, across all models.
+    pub fn usage_by_endpoint(&self) -> HashMap<Endpoint, ChatUsage> {
+        let mut by_endpoint = HashMap::<Endpoint, ChatUsage>::new();
+        for ((_, endpoint), (_, usage)) in self.entries.read().unwrap().iter() {
+            *by_endpoint.entry(*endpoint).or_default() += *usage;
+        }
+        by_endpoint
+    }
+
+    /// The total cost in dollars of everything in the ledger.
+    ///
+    /// Batch usage is priced at the batch discount. If the price of any of the models is unknown, this returns `None`.
+    pub fn cost(&self) -> Option<f64> {
+        self.entries().iter().map(|entry| entry.cost).sum()
+    }
+
+    /// Export the ledger as a JSON array of [`LedgerEntry`]s.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.entries()).unwrap()
+    }
+
+    /// Export the ledger as CSV, with one row per model and endpoint.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "model,endpoint,requests,prompt_tokens,cached_prompt_tokens,completion_tokens,total_tokens,cost\n",
+        );
+        for entry in self.entries() {
+            let model = if entry.model.contains([',', '"', '\n']) {
+                format!("\"{}\"", entry.model.replace('"', "\"\""))
+            } else {
+                entry.model
+            };
+            let cached_prompt_tokens = entry
+                .usage
+                .prompt_token_details
+                .map(|details| details.cached_tokens)
+                .unwrap_or_default();
+            let cost = entry.cost.map(|cost| cost.to_string()).unwrap_or_default();
+            csv.push_str(&format!(
+                "{model},{},{},{},{cached_prompt_tokens},{},{},{cost}\n",
+                entry.endpoint,
+                entry.requests,
+                entry.usage.prompt_tokens,
+                entry.usage.completion_tokens,
+                entry.usage.total_tokens,
+            ));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_usage_is_discounted() {
+        let ledger = UsageLedger::new();
+        let usage = ChatUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 1_000_000,
+            total_tokens: 2_000_000,
+            prompt_token_details: None,
+            completion_token_details: None,
+        };
+        ledger.record("gpt-4o", Endpoint::Chat, usage);
+        ledger.record("gpt-4.1", Endpoint::Chat, usage);
+        ledger.record("gpt-4o", Endpoint::Batch, usage);
+
+        // gpt-4o: 2.50 + 10.00, gpt-4.1: 2.00 + 8.00, batch gpt-4o: half of gpt-4o
+        assert_eq!(ledger.cost(), Some(12.50 + 10.00 + 6.25));
+        assert_eq!(ledger.usage().prompt_tokens, 3_000_000);
+        assert_eq!(ledger.usage_by_model()["gpt-4o"].prompt_tokens, 2_000_000);
+
+        let csv = ledger.to_csv();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.contains("gpt-4o,batch,1,1000000,0,1000000,2000000,6.25"));
+
+        ledger.reset();
+        assert_eq!(ledger.cost(), Some(0.0));
+        assert!(ledger.entries().is_empty());
+    }
+
+    #[test]
+    fn budget_refuses_requests() {
+        let ledger = UsageLedger::new();
+        let usage = ChatUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 1_000_000,
+            total_tokens: 2_000_000,
+            prompt_token_details: None,
+            completion_token_details: None,
+        };
+        assert!(ledger.check_budget("gpt-4o", Endpoint::Chat, usage).is_ok());
+
+        ledger.set_budget(Some(20.0));
+        assert!(ledger.check_budget("gpt-4o", Endpoint::Chat, usage).is_ok());
+        ledger.record("gpt-4o", Endpoint::Chat, usage);
+        assert_eq!(
+            ledger.check_budget("gpt-4o", Endpoint::Chat, usage),
+            Err(BudgetExceeded {
+                budget: 20.0,
+                spent: 12.5,
+                estimate: 12.5,
+            })
+        );
+        // the batch discount brings it back under the limit
+        assert!(ledger
+            .check_budget("gpt-4o", Endpoint::Batch, usage)
+            .is_ok());
+    }
+}
@@ -8,6 +8,16 @@ impl std::fmt::Display for OpenAiApiKeyError {
 }
 impl std::error::Error for OpenAiApiKeyError {}
 
+/// An error that occurs when the Anthropic API key is not found in the environment.
+#[derive(Debug)]
+pub struct AnthropicApiKeyError(#[expect(unused)] std::env::VarError);
+impl std::fmt::Display for AnthropicApiKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unable to find the Anthropic API key in the environment. Please set the ANTHROPIC_API_KEY environment variable. API keys can be found at <https://console.anthropic.com/settings/keys>.")
+    }
+}
+impl std::error::Error for AnthropicApiKeyError {}
+
 pub(crate) fn api_key() -> Result<String, OpenAiApiKeyError> {
     #[cfg(feature = "dotenvy")]
     {
@@ -17,6 +27,15 @@ pub(crate) fn api_key() -> Result<String, OpenAiApiKeyError> {
     std::env::var("OPENAI_API_KEY").map_err(OpenAiApiKeyError)
 }
 
+pub(crate) fn anthropic_api_key() -> Result<String, AnthropicApiKeyError> {
+    #[cfg(feature = "dotenvy")]
+    {
+        use dotenvy::dotenv;
+        dotenv().ok();
+    }
+    std::env::var("ANTHROPIC_API_KEY").map_err(AnthropicApiKeyError)
+}
+
 pub(crate) fn remove_trailing_slash(url: url::Url) -> url::Url {
     let mut url = url;
     let path = url.path().to_string();
@@ -24,3 +43,56 @@ pub(crate) fn remove_trailing_slash(url: url::Url) -> url::Url {
     url.set_path(path);
     url
 }
+
+/// How requests are authenticated with the API.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Authentication {
+    /// An `Authorization: Bearer <api key>` header. This is what OpenAI and most compatible APIs use.
+    #[default]
+    Bearer,
+    /// An `api-key` header, and an `api-version` query parameter on every request. This is what Azure OpenAI uses.
+    Azure {
+        /// The API version, such as `2024-10-21`.
+        api_version: String,
+    },
+}
+
+pub(crate) trait Authenticate {
+    fn authenticate(self, authentication: &Authentication, api_key: &str) -> Self;
+}
+
+impl Authenticate for reqwest::RequestBuilder {
+    fn authenticate(self, authentication: &Authentication, api_key: &str) -> Self {
+        match authentication {
+            Authentication::Bearer => self.header("Authorization", format!("Bearer {}", api_key)),
+            Authentication::Azure { api_version } => self
+                .header("api-key", api_key)
+                .query(&[("api-version", api_version)]),
+        }
+    }
+}
+
+/// Parses a URL, adding a trailing slash so that it can be joined with subpaths.
+///
+/// Panics if the argument is not a valid URL.
+pub(crate) fn base_url(url: impl Into<String>) -> url::Url {
+    let url = url.into();
+    let url = if url.ends_with('/') {
+        url
+    } else {
+        format!("{}/", url)
+    };
+    url::Url::parse(&url).unwrap()
+}
+
+/// Build a multipart part that streams the file at `path`, rather than reading it into memory first.
+pub(crate) async fn file_part(
+    path: &std::path::Path,
+    file_name: String,
+) -> std::io::Result<reqwest::multipart::Part> {
+    use tokio_util::codec::{BytesCodec, FramedRead};
+
+    let file = tokio::fs::File::open(path).await?;
+    let stream = FramedRead::new(file, BytesCodec::new());
+    Ok(reqwest::multipart::Part::stream(reqwest::Body::wrap_stream(stream)).file_name(file_name))
+}